@@ -0,0 +1,104 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*, primitives::Rectangle};
+use speedometer::{gauge, Dial, DisplaySink, GaugeSpec, Theme};
+
+// Frame buffer with a flush that copies into a second buffer, standing in for the
+// panel so the clear+draw+flush cycle can be timed without SPI
+struct MemoryDisplay {
+    buffer: Vec<Rgb565>,
+    panel: Vec<Rgb565>,
+}
+
+impl MemoryDisplay {
+    fn new() -> Self {
+        Self {
+            buffer: vec![Rgb565::BLACK; 240 * 240],
+            panel: vec![Rgb565::BLACK; 240 * 240],
+        }
+    }
+}
+
+impl OriginDimensions for MemoryDisplay {
+    fn size(&self) -> Size {
+        Size::new(240, 240)
+    }
+}
+
+impl DrawTarget for MemoryDisplay {
+    type Color = Rgb565;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let bounds = Rectangle::new(Point::zero(), self.size());
+        for Pixel(point, color) in pixels {
+            if bounds.contains(point) {
+                self.buffer[(point.y * 240 + point.x) as usize] = color;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DisplaySink for MemoryDisplay {
+    fn clear_buffer(&mut self) {
+        self.buffer.fill(Rgb565::BLACK);
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.panel.copy_from_slice(&self.buffer);
+        Ok(())
+    }
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let spec = GaugeSpec::default();
+    let theme = Theme::default();
+
+    let mut display = MemoryDisplay::new();
+
+    c.bench_function("full frame", |b| {
+        b.iter(|| gauge::render(&mut display, &spec, black_box(67.0)).unwrap())
+    });
+
+    c.bench_function("dial", |b| {
+        b.iter(|| gauge::draw_dial(&mut display, &spec, &theme).unwrap())
+    });
+
+    c.bench_function("needle and readout", |b| {
+        b.iter(|| {
+            gauge::draw_needle(&mut display, &spec, &theme, black_box(67.0)).unwrap();
+            gauge::draw_readout(&mut display, &spec, &theme, black_box(67.0)).unwrap();
+        })
+    });
+
+    let dial = Dial::new(spec.clone(), theme);
+    c.bench_function("cached dial frame", |b| {
+        b.iter(|| dial.draw(&mut display, black_box(67.0)).unwrap())
+    });
+
+    c.bench_function("value to angle", |b| {
+        b.iter(|| spec.angle(black_box(67.0)))
+    });
+
+    // one update from update_speed.sh: blank, redraw everything, push it out
+    c.bench_function("clear draw flush", |b| {
+        b.iter(|| {
+            display.clear_buffer();
+            gauge::render(&mut display, &spec, black_box(67.0)).unwrap();
+            display.flush().unwrap();
+        })
+    });
+
+    c.bench_function("cached dial draw flush", |b| {
+        b.iter(|| {
+            dial.draw(&mut display, black_box(67.0)).unwrap();
+            display.flush().unwrap();
+        })
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,18 @@
+// cargo +nightly fuzz run parse_speed
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use speedometer::{Speed, SpeedUnit};
+
+fuzz_target!(|data: &[u8]| {
+    // the file source hands over whatever was in the file, as text
+    let s = String::from_utf8_lossy(data);
+    if let Ok(speed) = s.parse::<Speed>() {
+        assert!(speed.value.is_finite() && speed.value >= 0.0);
+        for unit in [None, Some(SpeedUnit::Mph), Some(SpeedUnit::Kmh)] {
+            if let Ok(value) = speed.in_unit(unit) {
+                assert!(value.is_finite() && value >= 0.0);
+            }
+        }
+    }
+});
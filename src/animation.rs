@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Model {
+    // straight to the new speed, as the gauge always did
+    Snap,
+    // pulled towards the speed by a damped spring
+    #[default]
+    Spring,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnimationConfig {
+    pub model: Model,
+    // how quick the spring is, its undamped natural frequency
+    pub frequency_hz: f32,
+    // 1 is critically damped, the fastest there is without overshooting. Below 1
+    // overshoots a little and swings back, above 1 creeps in slower.
+    pub damping: f32,
+    // frames drawn per second while the needle moves
+    pub fps: u32,
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        Self { model: Model::Spring, frequency_hz: 1.5, damping: 1.0, fps: 30 }
+    }
+}
+
+impl AnimationConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.frequency_hz > 0.0 && self.frequency_hz <= 20.0) {
+            return Err(anyhow!("frequency_hz has to be above 0 and at most 20, got {}", self.frequency_hz));
+        }
+        if !(self.damping > 0.0 && self.damping <= 10.0) {
+            return Err(anyhow!("damping has to be above 0 and at most 10, got {}", self.damping));
+        }
+        if !(1..=60).contains(&self.fps) {
+            return Err(anyhow!("fps has to be from 1 to 60, got {}", self.fps));
+        }
+        Ok(())
+    }
+
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.fps
+    }
+}
+
+// Where the needle points while it catches up with the speed. Time only moves
+// in fixed steps, so the same targets and the same time give the same positions
+// however the time is handed over, which keeps it testable.
+pub struct Needle {
+    config: AnimationConfig,
+    position: f32,
+    velocity: f32,
+    target: f32,
+    // time handed over that did not make up a whole step yet
+    carry: Duration,
+    // close enough to call it there, a fraction of the scale
+    epsilon: f32,
+}
+
+impl Needle {
+    pub const STEP: Duration = Duration::from_millis(4);
+    // a stall (or the first move after a long rest) does not fast forward more than this
+    const MAX_ADVANCE: Duration = Duration::from_millis(250);
+
+    // At rest at the bottom of a scale from `min` to `max`
+    pub fn new(config: &AnimationConfig, min: f32, max: f32) -> Self {
+        let mut needle =
+            Self { config: config.clone(), position: min, velocity: 0.0, target: min, carry: Duration::ZERO, epsilon: 0.0 };
+        needle.set_scale(min, max);
+        needle
+    }
+
+    pub fn set_config(&mut self, config: &AnimationConfig) {
+        self.config = config.clone();
+        if config.model == Model::Snap {
+            self.settle();
+        }
+    }
+
+    pub fn set_scale(&mut self, min: f32, max: f32) {
+        self.epsilon = (max - min).abs() * 1e-4;
+    }
+
+    pub fn set_target(&mut self, target: f32) {
+        self.target = target;
+        if self.config.model == Model::Snap {
+            self.settle();
+        }
+    }
+
+    // Straight to the target
+    pub fn settle(&mut self) {
+        self.position = self.target;
+        self.velocity = 0.0;
+        self.carry = Duration::ZERO;
+    }
+
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    pub fn moving(&self) -> bool {
+        self.position != self.target || self.velocity != 0.0
+    }
+
+    pub fn advance(&mut self, elapsed: Duration) {
+        if !self.moving() {
+            return;
+        }
+        self.carry += elapsed.min(Self::MAX_ADVANCE);
+        // Semi-implicit Euler only holds together while both the spring (w) and the
+        // damping (2 z w) times the step stay well under 2, so a stiff or heavily
+        // damped needle takes each step in as many pieces as that needs
+        let omega = 2.0 * std::f32::consts::PI * self.config.frequency_hz;
+        let rate = omega.max(2.0 * self.config.damping * omega) * Self::STEP.as_secs_f32();
+        let pieces = (rate / 0.5).ceil().max(1.0) as u32;
+        let dt = Self::STEP.as_secs_f32() / pieces as f32;
+        while self.carry >= Self::STEP {
+            self.carry -= Self::STEP;
+            for _ in 0..pieces {
+                self.step(omega, dt);
+            }
+        }
+        if (self.target - self.position).abs() < self.epsilon && self.velocity.abs() < self.epsilon {
+            self.settle();
+        }
+    }
+
+    // One step of x'' = w^2 (target - x) - 2 z w x'
+    fn step(&mut self, omega: f32, dt: f32) {
+        let accel = omega * omega * (self.target - self.position) - 2.0 * self.config.damping * omega * self.velocity;
+        self.velocity += accel * dt;
+        self.position += self.velocity * dt;
+    }
+}
@@ -0,0 +1,149 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+
+use crate::{
+    config::{FailoverSource, SourceKind},
+    speed::Speed,
+};
+
+struct Slot {
+    kind: SourceKind,
+    // None never goes stale, for sources that only speak up on a change
+    stale: Option<Duration>,
+    // time of the last good reading, None after an error
+    last_good: Option<Instant>,
+    speed: Option<Speed>,
+    ended: bool,
+}
+
+impl Slot {
+    fn fresh(&self, now: Instant) -> bool {
+        match (self.last_good, self.stale) {
+            _ if self.ended => false,
+            (Some(at), Some(stale)) => now.saturating_duration_since(at) < stale,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+// Picks which of several sources the gauge shows: the first one in the list that
+// has given a good reading within its `stale_ms`. When it goes quiet or reports a
+// problem the next one takes over, and it takes back over with its next reading.
+//
+// The main loop hands every reading over as it comes in and calls `check` in
+// between. Both return what the gauge should show now, if that changed: a speed,
+// or the error when no source has one.
+pub struct Arbiter {
+    slots: Vec<Slot>,
+    active: Option<usize>,
+}
+
+impl Arbiter {
+    pub fn new(sources: &[FailoverSource]) -> Self {
+        let slots = sources
+            .iter()
+            .map(|s| Slot {
+                kind: s.source,
+                stale: Some(Duration::from_millis(s.stale_ms)).filter(|d| !d.is_zero()),
+                last_good: None,
+                speed: None,
+                ended: false,
+            })
+            .collect();
+        Self { slots, active: None }
+    }
+
+    // One source that never goes stale, nothing to arbitrate
+    pub fn single(source: SourceKind) -> Self {
+        Self::new(&[FailoverSource { source, stale_ms: 0 }])
+    }
+
+    pub fn sources(&self) -> Vec<SourceKind> {
+        self.slots.iter().map(|s| s.kind).collect()
+    }
+
+    pub fn active(&self) -> Option<SourceKind> {
+        self.active.map(|i| self.slots[i].kind)
+    }
+
+    // The source shown on the dial, only worth showing when there is a choice
+    pub fn badge(&self) -> Option<String> {
+        match self.slots.len() {
+            0 | 1 => None,
+            _ => self.active().map(|kind| kind.to_string().to_uppercase()),
+        }
+    }
+
+    pub fn reading(&mut self, index: usize, reading: Result<Speed>, now: Instant) -> Option<Result<Speed>> {
+        let many = self.slots.len() > 1;
+        let slot = &mut self.slots[index];
+        match &reading {
+            Ok(speed) => {
+                slot.last_good = Some(now);
+                slot.speed = Some(*speed);
+            },
+            Err(e) => {
+                slot.last_good = None;
+                match self.active {
+                    Some(i) if i == index && many => warn!("{}: {:#}", slot.kind, e),
+                    _ => debug!("{} is down: {:#}", slot.kind, e),
+                }
+            },
+        }
+
+        let changed = self.select(now);
+        match reading {
+            Ok(speed) if self.active == Some(index) => Some(Ok(speed)),
+            // nothing to show instead, so the problem is what there is to show
+            Err(e) if self.active.is_none() => Some(Err(e)),
+            _ if changed => self.shown(),
+            _ => None,
+        }
+    }
+
+    // The source has nothing more to give
+    pub fn ended(&mut self, index: usize, now: Instant) -> Option<Result<Speed>> {
+        self.slots[index].ended = true;
+        self.check(now)
+    }
+
+    pub fn all_ended(&self) -> bool {
+        self.slots.iter().all(|s| s.ended)
+    }
+
+    // Fails over from a source that went stale
+    pub fn check(&mut self, now: Instant) -> Option<Result<Speed>> {
+        if self.select(now) {
+            self.shown()
+        } else {
+            None
+        }
+    }
+
+    fn shown(&self) -> Option<Result<Speed>> {
+        match self.active {
+            Some(i) => self.slots[i].speed.map(Ok),
+            None => Some(Err(anyhow!("no speed from any source"))),
+        }
+    }
+
+    // Whether the active source changed
+    fn select(&mut self, now: Instant) -> bool {
+        let active = self.slots.iter().position(|s| s.fresh(now));
+        if active == self.active {
+            return false;
+        }
+        if self.slots.len() > 1 {
+            match (self.active, active) {
+                (_, Some(i)) => info!("Showing the speed from {}", self.slots[i].kind),
+                (Some(i), None) => warn!("Lost {}, no other source has a speed", self.slots[i].kind),
+                (None, None) => {},
+            }
+        }
+        self.active = active;
+        true
+    }
+}
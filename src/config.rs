@@ -0,0 +1,357 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context, Result};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Deserialize;
+
+use crate::{
+    animation::AnimationConfig,
+    filter::FilterConfig,
+    hal::Rotation,
+    source::{
+        CanConfig, GpsdConfig, HttpConfig, MqttConfig, NmeaConfig, ObdConfig, PulseConfig, SerialConfig, UdpConfig,
+        UnixConfig, WatchMode,
+    },
+    spec::GaugeSpec,
+    theme::Theme,
+};
+
+// Everything that differs between units, read from a TOML file. Every section and
+// key is optional and falls back to the original wiring and look, see
+// speedometer.toml for the full list.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub display: DisplayConfig,
+    pub input: InputConfig,
+    pub gauge: GaugeSpec,
+    pub theme: Theme,
+    pub animation: AnimationConfig,
+    // applied in order to every speed before it is shown, none by default
+    pub filter: Vec<FilterConfig>,
+}
+
+// SPI and GPIO wiring of the panel, pins are BCM numbers
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    pub spi_bus: u8,
+    pub spi_chip_select: u8,
+    pub spi_speed_hz: u32,
+    pub cs_pin: u8,
+    pub dc_pin: u8,
+    pub reset_pin: u8,
+    pub pwm_channel: u8,
+    // backlight level, 0 (off) to 255
+    pub brightness: u8,
+    // 0, 90, 180 or 270 degrees clockwise
+    pub rotation: Rotation,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            spi_bus: 0,
+            spi_chip_select: 0,
+            spi_speed_hz: 27_000_000,
+            cs_pin: 8,
+            dc_pin: 25,
+            reset_pin: 27,
+            pwm_channel: 0,
+            brightness: 255,
+            rotation: Rotation::Deg0,
+        }
+    }
+}
+
+// Where the speed comes from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceKind {
+    #[default]
+    File,
+    Udp,
+    Serial,
+    Nmea,
+    Gpsd,
+    Obd,
+    Can,
+    Pulse,
+    Stdin,
+    Unix,
+    Mqtt,
+    Http,
+}
+
+impl FromStr for SourceKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "file" => Ok(SourceKind::File),
+            "udp" => Ok(SourceKind::Udp),
+            "serial" => Ok(SourceKind::Serial),
+            "nmea" => Ok(SourceKind::Nmea),
+            "gpsd" => Ok(SourceKind::Gpsd),
+            "obd" => Ok(SourceKind::Obd),
+            "can" => Ok(SourceKind::Can),
+            "pulse" => Ok(SourceKind::Pulse),
+            "stdin" => Ok(SourceKind::Stdin),
+            "unix" => Ok(SourceKind::Unix),
+            "mqtt" => Ok(SourceKind::Mqtt),
+            "http" => Ok(SourceKind::Http),
+            _ => Err(anyhow!(
+                "unknown source \"{}\", expected file, udp, serial, nmea, gpsd, obd, can, pulse, stdin, unix, mqtt or http",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SourceKind::File => "file",
+            SourceKind::Udp => "udp",
+            SourceKind::Serial => "serial",
+            SourceKind::Nmea => "nmea",
+            SourceKind::Gpsd => "gpsd",
+            SourceKind::Obd => "obd",
+            SourceKind::Can => "can",
+            SourceKind::Pulse => "pulse",
+            SourceKind::Stdin => "stdin",
+            SourceKind::Unix => "unix",
+            SourceKind::Mqtt => "mqtt",
+            SourceKind::Http => "http",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// One of the sources in [[input.failover]], set up by its own section as usual
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FailoverSource {
+    pub source: SourceKind,
+    // without a good reading for this long the next source takes over, 0 never
+    // goes stale (the file source only reads when the file changes)
+    #[serde(default = "default_stale_ms")]
+    pub stale_ms: u64,
+}
+
+fn default_stale_ms() -> u64 {
+    2000
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputConfig {
+    pub source: SourceKind,
+    // several sources to fail over between, the first one is preferred. Empty
+    // for just `source`.
+    pub failover: Vec<FailoverSource>,
+    // without a good sample for this long the gauge shows that the signal is
+    // lost, 0 never does
+    pub signal_timeout_ms: u64,
+    // text file holding the current speed
+    pub path: PathBuf,
+    // "modify" reads on every change, "close_write" only once the writer is done
+    pub watch: WatchMode,
+    pub udp: UdpConfig,
+    pub serial: SerialConfig,
+    pub nmea: NmeaConfig,
+    pub gpsd: GpsdConfig,
+    pub obd: ObdConfig,
+    pub can: CanConfig,
+    pub pulse: PulseConfig,
+    pub unix: UnixConfig,
+    pub mqtt: MqttConfig,
+    pub http: HttpConfig,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            source: SourceKind::File,
+            failover: Vec::new(),
+            signal_timeout_ms: 0,
+            path: PathBuf::from("./data/speed.txt"),
+            watch: WatchMode::Modify,
+            udp: UdpConfig::default(),
+            serial: SerialConfig::default(),
+            nmea: NmeaConfig::default(),
+            gpsd: GpsdConfig::default(),
+            obd: ObdConfig::default(),
+            can: CanConfig::default(),
+            pulse: PulseConfig::default(),
+            unix: UnixConfig::default(),
+            mqtt: MqttConfig::default(),
+            http: HttpConfig::default(),
+        }
+    }
+}
+
+impl InputConfig {
+    // The sources to run, in order of preference
+    pub fn sources(&self) -> Vec<FailoverSource> {
+        if self.failover.is_empty() {
+            vec![FailoverSource { source: self.source, stale_ms: 0 }]
+        } else {
+            self.failover.clone()
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let s = fs::read_to_string(path).with_context(|| format!("Unable to read config {}", path.display()))?;
+        let config: Config = toml::from_str(&s).with_context(|| format!("Invalid config {}", path.display()))?;
+        config.validate().with_context(|| format!("Invalid config {}", path.display()))?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.display.validate().context("[display]")?;
+        self.gauge.validate().context("[gauge]")?;
+        self.animation.validate().context("[animation]")?;
+        for filter in &self.filter {
+            filter.validate().context("[[filter]]")?;
+        }
+        for signal in &self.input.can.signals {
+            signal.validate().context("[input.can]")?;
+        }
+        for (i, failover) in self.input.failover.iter().enumerate() {
+            if self.input.failover[..i].iter().any(|f| f.source == failover.source) {
+                return Err(anyhow!("[[input.failover]] has {} more than once", failover.source));
+            }
+        }
+        self.input.pulse.validate().context("[input.pulse]")?;
+        self.input.mqtt.validate().context("[input.mqtt]")?;
+        if self.input.sources().iter().any(|s| s.source == SourceKind::Pulse) {
+            let d = &self.display;
+            let (mosi, sclk) = d.spi_pins();
+            let taken = [
+                ("cs_pin", d.cs_pin),
+                ("dc_pin", d.dc_pin),
+                ("reset_pin", d.reset_pin),
+                ("the backlight PWM", d.pwm_pin()),
+                ("SPI MOSI", mosi),
+                ("SPI SCLK", sclk),
+            ];
+            if let Some((name, pin)) = taken.iter().find(|(_, pin)| *pin == self.input.pulse.pin) {
+                return Err(anyhow!("[input.pulse] pin {} is already wired to the display, as {}", pin, name));
+            }
+        }
+        Ok(())
+    }
+
+    // Whether switching to `other` needs a restart, only the gauge, theme,
+    // animation and filters are picked up on the fly
+    pub fn needs_restart(&self, other: &Config) -> bool {
+        self.display.without_brightness() != other.display.without_brightness() || self.input != other.input
+    }
+}
+
+impl DisplayConfig {
+    pub fn validate(&self) -> Result<()> {
+        let d = self;
+        if d.spi_bus > 6 {
+            return Err(anyhow!("spi_bus has to be 0 to 6, got {}", d.spi_bus));
+        }
+        if d.spi_chip_select > 15 {
+            return Err(anyhow!("spi_chip_select has to be 0 to 15, got {}", d.spi_chip_select));
+        }
+        if d.spi_speed_hz == 0 {
+            return Err(anyhow!("spi_speed_hz has to be above 0"));
+        }
+        if d.pwm_channel > 1 {
+            return Err(anyhow!("pwm_channel has to be 0 or 1, got {}", d.pwm_channel));
+        }
+
+        let pins = [("cs_pin", d.cs_pin), ("dc_pin", d.dc_pin), ("reset_pin", d.reset_pin)];
+        for (name, pin) in pins {
+            // the 40 pin header brings out GPIO 0 to 27
+            if pin > 27 {
+                return Err(anyhow!("{} has to be a GPIO from 0 to 27, got {}", name, pin));
+            }
+        }
+        for (i, (name, pin)) in pins.iter().enumerate() {
+            if let Some((other, _)) = pins[i + 1..].iter().find(|(_, p)| p == pin) {
+                return Err(anyhow!("{} and {} are both GPIO {}", name, other, pin));
+            }
+        }
+        Ok(())
+    }
+
+    // The GPIO the backlight PWM comes out on
+    pub fn pwm_pin(&self) -> u8 {
+        match self.pwm_channel {
+            0 => 18,
+            _ => 19,
+        }
+    }
+
+    // MOSI and SCLK of the SPI bus, SPI3 to SPI6 are only on the Pi 4 and later
+    pub fn spi_pins(&self) -> (u8, u8) {
+        match self.spi_bus {
+            0 => (10, 11),
+            1 => (20, 21),
+            3 => (2, 3),
+            4 => (6, 7),
+            5 => (14, 15),
+            _ => (20, 21),
+        }
+    }
+
+    fn without_brightness(&self) -> DisplayConfig {
+        DisplayConfig { brightness: 0, ..self.clone() }
+    }
+}
+
+// Calls back with the reloaded config every time the file is saved. The directory
+// is watched rather than the file, editors often save by replacing the file,
+// which would leave a watch on the file itself pointing at the old one.
+pub struct ConfigWatcher {
+    // the watcher stops delivering events once dropped
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    pub fn new<F>(path: impl AsRef<Path>, mut on_change: F) -> Result<Self>
+    where
+        F: FnMut(Result<Config>) + Send + 'static,
+    {
+        let path = path.as_ref().to_path_buf();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let file_name = path.file_name().ok_or_else(|| anyhow!("{} is not a file", path.display()))?.to_owned();
+
+        let mut watcher = RecommendedWatcher::new(
+            move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                let ours = event.paths.iter().any(|p| p.file_name() == Some(file_name.as_os_str()));
+                if !ours || !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    return;
+                }
+                // saving truncates first, wait for the write that follows
+                if fs::metadata(&path).map(|m| m.len() == 0).unwrap_or(false) {
+                    return;
+                }
+                on_change(Config::load(&path));
+            },
+            notify::Config::default(),
+        )?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+
+        Ok(Self { _watcher: watcher })
+    }
+}
@@ -0,0 +1,206 @@
+use std::{collections::VecDeque, time::Instant};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+// One step of the chain, in the order listed in the config
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum FilterConfig {
+    // mean of the last `window` samples
+    MovingAverage { window: usize },
+    // each sample moves the output `alpha` of the way there, 1 is no smoothing
+    Ema { alpha: f32 },
+    // middle of the last `window` samples, a lone spike never gets through
+    Median { window: usize },
+    // the output never changes faster than this many units a second
+    RateLimit { max_per_s: f32 },
+    // how much the real speed drifts (units² a second) against how noisy the
+    // samples are (units²)
+    Kalman { process_noise: f32, measurement_noise: f32 },
+}
+
+impl FilterConfig {
+    pub fn validate(&self) -> Result<()> {
+        match *self {
+            FilterConfig::MovingAverage { window } | FilterConfig::Median { window } if !(1..=100).contains(&window) => {
+                Err(anyhow!("window has to be from 1 to 100, got {}", window))
+            },
+            FilterConfig::Ema { alpha } if !(alpha > 0.0 && alpha <= 1.0) => {
+                Err(anyhow!("alpha has to be above 0 and at most 1, got {}", alpha))
+            },
+            FilterConfig::RateLimit { max_per_s } if !(max_per_s > 0.0 && max_per_s.is_finite()) => {
+                Err(anyhow!("max_per_s has to be above 0, got {}", max_per_s))
+            },
+            FilterConfig::Kalman { process_noise, measurement_noise }
+                if ![process_noise, measurement_noise].iter().all(|n| n.is_finite() && *n > 0.0) =>
+            {
+                Err(anyhow!("process_noise and measurement_noise have to be numbers above 0"))
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+pub trait Filter: Send {
+    // The filtered value for a sample taken at `at`
+    fn apply(&mut self, value: f32, at: Instant) -> f32;
+}
+
+pub struct MovingAverage {
+    window: usize,
+    samples: VecDeque<f32>,
+}
+
+impl MovingAverage {
+    pub fn new(window: usize) -> Self {
+        Self { window: window.max(1), samples: VecDeque::new() }
+    }
+}
+
+impl Filter for MovingAverage {
+    fn apply(&mut self, value: f32, _at: Instant) -> f32 {
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(value);
+        self.samples.iter().sum::<f32>() / self.samples.len() as f32
+    }
+}
+
+pub struct Ema {
+    alpha: f32,
+    value: Option<f32>,
+}
+
+impl Ema {
+    pub fn new(alpha: f32) -> Self {
+        Self { alpha, value: None }
+    }
+}
+
+impl Filter for Ema {
+    fn apply(&mut self, value: f32, _at: Instant) -> f32 {
+        // starts from the first sample rather than easing up from 0
+        let next = match self.value {
+            Some(last) => last + self.alpha * (value - last),
+            None => value,
+        };
+        self.value = Some(next);
+        next
+    }
+}
+
+pub struct Median {
+    window: usize,
+    samples: VecDeque<f32>,
+}
+
+impl Median {
+    pub fn new(window: usize) -> Self {
+        Self { window: window.max(1), samples: VecDeque::new() }
+    }
+}
+
+impl Filter for Median {
+    fn apply(&mut self, value: f32, _at: Instant) -> f32 {
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(value);
+        let mut sorted: Vec<f32> = self.samples.iter().copied().collect();
+        sorted.sort_by(f32::total_cmp);
+        let mid = sorted.len() / 2;
+        match sorted.len() % 2 {
+            0 => (sorted[mid - 1] + sorted[mid]) / 2.0,
+            _ => sorted[mid],
+        }
+    }
+}
+
+pub struct RateLimit {
+    max_per_s: f32,
+    last: Option<(f32, Instant)>,
+}
+
+impl RateLimit {
+    pub fn new(max_per_s: f32) -> Self {
+        Self { max_per_s, last: None }
+    }
+}
+
+impl Filter for RateLimit {
+    fn apply(&mut self, value: f32, at: Instant) -> f32 {
+        let next = match self.last {
+            Some((last, then)) => {
+                let step = self.max_per_s * at.saturating_duration_since(then).as_secs_f32();
+                value.clamp(last - step, last + step)
+            },
+            None => value,
+        };
+        self.last = Some((next, at));
+        next
+    }
+}
+
+// Estimates a speed that wanders slowly, from samples with noise on top. The
+// uncertainty grows with the time between samples, so a sample after a gap
+// counts for more than one straight after the last.
+pub struct Kalman {
+    process_noise: f32,
+    measurement_noise: f32,
+    // estimate, its variance and when it was made
+    state: Option<(f32, f32, Instant)>,
+}
+
+impl Kalman {
+    pub fn new(process_noise: f32, measurement_noise: f32) -> Self {
+        Self { process_noise, measurement_noise, state: None }
+    }
+}
+
+impl Filter for Kalman {
+    fn apply(&mut self, value: f32, at: Instant) -> f32 {
+        let (estimate, variance) = match self.state {
+            Some((estimate, variance, then)) => {
+                let predicted = variance + self.process_noise * at.saturating_duration_since(then).as_secs_f32();
+                let gain = predicted / (predicted + self.measurement_noise);
+                (estimate + gain * (value - estimate), (1.0 - gain) * predicted)
+            },
+            // the first sample is all there is to go on
+            None => (value, self.measurement_noise),
+        };
+        self.state = Some((estimate, variance, at));
+        estimate
+    }
+}
+
+// The filters from the config, each taking what the one before gives
+#[derive(Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn Filter>>,
+}
+
+impl FilterChain {
+    pub fn new(configs: &[FilterConfig]) -> Self {
+        let filters = configs
+            .iter()
+            .map(|config| -> Box<dyn Filter> {
+                match *config {
+                    FilterConfig::MovingAverage { window } => Box::new(MovingAverage::new(window)),
+                    FilterConfig::Ema { alpha } => Box::new(Ema::new(alpha)),
+                    FilterConfig::Median { window } => Box::new(Median::new(window)),
+                    FilterConfig::RateLimit { max_per_s } => Box::new(RateLimit::new(max_per_s)),
+                    FilterConfig::Kalman { process_noise, measurement_noise } => {
+                        Box::new(Kalman::new(process_noise, measurement_noise))
+                    },
+                }
+            })
+            .collect();
+        Self { filters }
+    }
+
+    pub fn apply(&mut self, value: f32, at: Instant) -> f32 {
+        self.filters.iter_mut().fold(value, |value, filter| filter.apply(value, at))
+    }
+}
@@ -0,0 +1,161 @@
+use std::{fs, io::{BufWriter, Write}, path::Path};
+
+use anyhow::{anyhow, Result};
+use embedded_graphics::{
+    pixelcolor::{raw::RawU16, Rgb565},
+    prelude::*,
+    primitives::Rectangle,
+};
+
+use crate::hal::{DisplaySink, Rotation};
+
+// A 240x240 RGB565 frame held in memory
+#[derive(Clone)]
+pub struct Framebuffer {
+    pixels: Vec<Rgb565>,
+}
+
+impl Framebuffer {
+    pub const WIDTH: u32 = 240;
+    pub const HEIGHT: u32 = 240;
+
+    pub fn new() -> Self {
+        Self {
+            pixels: vec![Rgb565::BLACK; (Self::WIDTH * Self::HEIGHT) as usize],
+        }
+    }
+
+    // Big endian RGB565, the same layout as the files in assets/
+    pub fn from_raw(bytes: &[u8]) -> Result<Self> {
+        let expected = (Self::WIDTH * Self::HEIGHT * 2) as usize;
+        if bytes.len() != expected {
+            return Err(anyhow!("Expected {} bytes of RGB565, got {}", expected, bytes.len()));
+        }
+        let pixels = bytes
+            .chunks_exact(2)
+            .map(|b| Rgb565::from(RawU16::new(u16::from_be_bytes([b[0], b[1]]))))
+            .collect();
+        Ok(Self { pixels })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|e| anyhow!("Error reading {}: {}", path.display(), e))?;
+        Self::from_raw(&bytes)
+    }
+
+    pub fn to_raw(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|p| p.into_storage().to_be_bytes()).collect()
+    }
+
+    pub fn pixels(&self) -> &[Rgb565] {
+        &self.pixels
+    }
+
+    // The pixels inside `area` row by row, `area` has to be on screen
+    pub fn area<'a>(&'a self, area: &Rectangle) -> impl Iterator<Item = Rgb565> + 'a {
+        area.points().map(move |p| self.pixels[(p.y as u32 * Self::WIDTH + p.x as u32) as usize])
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.fill(Rgb565::BLACK);
+    }
+
+    // The frame turned clockwise, as a panel mounted at `rotation` would show it
+    pub fn rotated(&self, rotation: Rotation) -> Framebuffer {
+        let (w, h) = (Self::WIDTH as usize, Self::HEIGHT as usize);
+        let mut rotated = Framebuffer::new();
+        for y in 0..h {
+            for x in 0..w {
+                let (sx, sy) = match rotation {
+                    Rotation::Deg0 => (x, y),
+                    Rotation::Deg90 => (y, h - 1 - x),
+                    Rotation::Deg180 => (w - 1 - x, h - 1 - y),
+                    Rotation::Deg270 => (w - 1 - y, x),
+                };
+                rotated.pixels[y * w + x] = self.pixels[sy * w + sx];
+            }
+        }
+        rotated
+    }
+
+    // 8 bit RGB triplets, row by row
+    fn rgb888(&self) -> Vec<u8> {
+        let mut rgb = Vec::with_capacity(self.pixels.len() * 3);
+        for pixel in &self.pixels {
+            // scale 5/6/5 bit channels up to 8 bits
+            rgb.push((pixel.r() << 3) | (pixel.r() >> 2));
+            rgb.push((pixel.g() << 2) | (pixel.g() >> 4));
+            rgb.push((pixel.b() << 3) | (pixel.b() >> 2));
+        }
+        rgb
+    }
+
+    // Writes the frame as a binary PPM
+    pub fn write_ppm(&self, path: &Path) -> std::io::Result<()> {
+        let mut file = fs::File::create(path)?;
+        write!(file, "P6\n{} {}\n255\n", Self::WIDTH, Self::HEIGHT)?;
+        file.write_all(&self.rgb888())
+    }
+
+    pub fn write_png(&self, path: &Path) -> Result<()> {
+        let file = fs::File::create(path)?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), Self::WIDTH, Self::HEIGHT);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgb888())?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    // Picks the format from the extension: .png, .ppm or .raw (RGB565 like assets/)
+    pub fn save(&self, path: &Path) -> Result<()> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("png") => self.write_png(path),
+            Some("ppm") => Ok(self.write_ppm(path)?),
+            Some("raw") => Ok(fs::write(path, self.to_raw())?),
+            _ => Err(anyhow!("Unknown image format for {}, use .png, .ppm or .raw", path.display())),
+        }
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OriginDimensions for Framebuffer {
+    fn size(&self) -> Size {
+        Size::new(Self::WIDTH, Self::HEIGHT)
+    }
+}
+
+impl DrawTarget for Framebuffer {
+    type Color = Rgb565;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if point.x >= 0 && point.y >= 0 && (point.x as u32) < Self::WIDTH && (point.y as u32) < Self::HEIGHT {
+                self.pixels[(point.y as u32 * Self::WIDTH + point.x as u32) as usize] = color;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Headless use, the frame just stays in memory
+impl DisplaySink for Framebuffer {
+    fn clear_buffer(&mut self) {
+        self.clear();
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
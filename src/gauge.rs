@@ -0,0 +1,443 @@
+use std::path::Path;
+
+use anyhow::Result;
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    pixelcolor::Rgb565,
+    prelude::*,
+    primitives::{Circle, Line, PrimitiveStyle, Rectangle, Styled, Triangle},
+    mono_font::MonoTextStyle,
+    text::Text,
+};
+
+use crate::{framebuffer::Framebuffer, spec::GaugeSpec, theme::Theme};
+
+
+// Constants and precomputed values
+const TICK_LENGTH: i32 = 20;
+const DEFAULT_TEXT_RADIUS: i32 = 15;
+const TEXT_OFFSET_Y: i32 = 40;
+const UNIT_OFFSET_Y: i32 = 60;
+const STATUS_OFFSET_Y: i32 = 84;
+// the ring is about this wide where the status line sits
+const STATUS_WIDTH: u32 = 120;
+const BADGE_OFFSET_Y: i32 = -30;
+// the warning sign shown with no signal, centred this far above the hub
+const ICON_OFFSET_Y: i32 = -56;
+
+
+pub fn draw_speedometer<Display>(
+    display: &mut Display, 
+    spec: &GaugeSpec,
+    theme: &Theme,
+    speed: f32
+) -> Result<(), Display::Error>
+where
+    Display: DrawTarget<Color = Rgb565>,
+{
+    draw_dial(display, spec, theme)?;
+    draw_needle(display, spec, theme, speed)?;
+    draw_readout(display, spec, theme, speed)
+}
+
+// The parts of the gauge that do not depend on the speed: ring, ticks, numbers and unit
+pub fn draw_dial<Display>(
+    display: &mut Display,
+    spec: &GaugeSpec,
+    theme: &Theme
+) -> Result<(), Display::Error>
+where
+    Display: DrawTarget<Color = Rgb565>,
+{
+    let text_style = theme.text_style();
+    let unit_text_style = theme.unit_text_style();
+    let center = spec.center;
+    let radius = spec.radius as i32;
+
+    // Draw the dial
+    Circle::new(center - Point::new(radius - 1, radius - 1), spec.radius * 2)
+        .into_styled(theme.circle_style())
+        .draw(display)?;
+
+    let char_size = text_style.font.character_size;
+    for tick in spec.ticks() {
+        let angle = tick.angle;
+        let outer_end = center + Point::new(
+            (angle.cos() * radius as f32) as i32,
+            (angle.sin() * radius as f32) as i32,
+        );
+        let inner_end = center + Point::new(
+            (angle.cos() * (radius - TICK_LENGTH) as f32) as i32,
+            (angle.sin() * (radius - TICK_LENGTH) as f32) as i32,
+        );
+
+        Line::new(outer_end, inner_end)
+            .into_styled(theme.tick_style(tick.major))
+            .draw(display)?;
+        
+        if tick.label {
+            let label = format_label(tick.value);
+            let number_width = label.len() as i32 * char_size.width as i32;
+            let text_offset = Point::new(number_width / 2, (char_size.height as i32 + 1) / 2);
+            let additional_offset = Point::new(1, 9);
+            let text_angle = angle + std::f32::consts::PI;
+            let text_pos = center - Point::new(
+                (text_angle.cos() * (radius - (DEFAULT_TEXT_RADIUS + TICK_LENGTH)) as f32) as i32, 
+                (text_angle.sin() * (radius - (DEFAULT_TEXT_RADIUS + TICK_LENGTH)) as f32) as i32
+            ) - text_offset + additional_offset;
+            Text::new(&label, text_pos, text_style).draw(display)?;
+        }
+    }
+
+    // Display unit as text
+    let unit_width = spec.unit.len() as i32 * unit_text_style.font.character_size.width as i32;
+    let unit_pos = center + Point::new(1 - unit_width / 2, UNIT_OFFSET_Y);
+    Text::new(&spec.unit, unit_pos, unit_text_style).draw(display)?;
+
+    Ok(())
+}
+
+// Whole numbers without the trailing .0, anything else as is
+fn format_label(value: f32) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value as i64)
+    } else {
+        format!("{}", value)
+    }
+}
+
+// Whole numbers from 10 up and one decimal below, so converted readings like
+// 24.854849 still fit under the hub
+fn format_readout(speed: f32) -> String {
+    if speed.abs() >= 10.0 {
+        format!("{}", speed.round() as i64)
+    } else {
+        format_label((speed * 10.0).round() / 10.0)
+    }
+}
+
+pub fn draw_needle<Display>(display: &mut Display, spec: &GaugeSpec, theme: &Theme, speed: f32) -> Result<(), Display::Error>
+where
+    Display: DrawTarget<Color = Rgb565>,
+{
+    needle(spec, speed, theme.needle_style()).draw(display)
+}
+
+fn needle(spec: &GaugeSpec, speed: f32, style: PrimitiveStyle<Rgb565>) -> Styled<Line, PrimitiveStyle<Rgb565>> {
+    // Calculate needle position based on speed
+    let angle = spec.angle(speed);
+    let needle_end = spec.center + Point::new(
+        (angle.cos() * spec.needle_length as f32) as i32,
+        (angle.sin() * spec.needle_length as f32) as i32,
+    );
+
+    Line::new(spec.center, needle_end).into_styled(style)
+}
+
+// The digital speed under the needle hub
+pub fn draw_readout<Display>(
+    display: &mut Display,
+    spec: &GaugeSpec,
+    theme: &Theme,
+    speed: f32
+) -> Result<(), Display::Error>
+where
+    Display: DrawTarget<Color = Rgb565>,
+{
+    let speed_text_style = theme.speed_text_style();
+    let (speed_text, text_pos) = readout(spec, speed, &speed_text_style);
+    Text::new(&speed_text, text_pos, speed_text_style).draw(display)?;
+
+    Ok(())
+}
+
+fn readout(spec: &GaugeSpec, speed: f32, speed_text_style: &MonoTextStyle<'_, Rgb565>) -> (String, Point) {
+    readout_text(spec, format_readout(speed), speed_text_style)
+}
+
+fn readout_text(spec: &GaugeSpec, speed_text: String, speed_text_style: &MonoTextStyle<'_, Rgb565>) -> (String, Point) {
+    let char_size = speed_text_style.font.character_size;
+
+    // Display speed as text
+    let speed_text_width = speed_text.len().min(3) as i32 * char_size.width as i32;
+    let text_offset = Point::new(speed_text_width / 2, (char_size.height / 2) as i32);
+    let text_pos = spec.center - text_offset + Point::new(1, TEXT_OFFSET_Y);
+
+    (speed_text, text_pos)
+}
+
+// A short line under the unit for problems with the input, cut to fit the dial
+pub fn draw_status<Display>(
+    display: &mut Display,
+    spec: &GaugeSpec,
+    theme: &Theme,
+    status: &str
+) -> Result<(), Display::Error>
+where
+    Display: DrawTarget<Color = Rgb565>,
+{
+    let status_text_style = theme.status_text_style();
+    let (status_text, text_pos) = status_line(spec, status, &status_text_style);
+    Text::new(&status_text, text_pos, status_text_style).draw(display)?;
+
+    Ok(())
+}
+
+fn status_line(spec: &GaugeSpec, status: &str, status_text_style: &MonoTextStyle<'_, Rgb565>) -> (String, Point) {
+    let char_width = status_text_style.font.character_size.width;
+    let max_chars = (STATUS_WIDTH / char_width) as usize;
+    let status_text: String = match status.chars().count() {
+        n if n > max_chars => status.chars().take(max_chars - 2).chain("..".chars()).collect(),
+        _ => status.to_string(),
+    };
+    let status_width = status_text.chars().count() as i32 * char_width as i32;
+    let text_pos = spec.center + Point::new(1 - status_width / 2, STATUS_OFFSET_Y);
+
+    (status_text, text_pos)
+}
+
+// The source the speed comes from, above the hub
+pub fn draw_badge<Display>(
+    display: &mut Display,
+    spec: &GaugeSpec,
+    theme: &Theme,
+    badge: &str
+) -> Result<(), Display::Error>
+where
+    Display: DrawTarget<Color = Rgb565>,
+{
+    let text_style = theme.text_style();
+    let text_pos = badge_pos(spec, badge, &text_style);
+    Text::new(badge, text_pos, text_style).draw(display)?;
+
+    Ok(())
+}
+
+fn badge_pos(spec: &GaugeSpec, badge: &str, text_style: &MonoTextStyle<'_, Rgb565>) -> Point {
+    let badge_width = badge.chars().count() as i32 * text_style.font.character_size.width as i32;
+    spec.center + Point::new(1 - badge_width / 2, BADGE_OFFSET_Y)
+}
+
+// In place of the needle and readout when the speed stopped coming: the needle
+// greyed out where it was, dashes for the readout and a warning sign
+pub fn draw_signal_lost<Display>(
+    display: &mut Display,
+    spec: &GaugeSpec,
+    theme: &Theme,
+    speed: f32
+) -> Result<(), Display::Error>
+where
+    Display: DrawTarget<Color = Rgb565>,
+{
+    needle(spec, speed, theme.lost_needle_style()).draw(display)?;
+
+    let lost_text_style = theme.lost_text_style();
+    let (text, text_pos) = readout_text(spec, "--".to_string(), &lost_text_style);
+    Text::new(&text, text_pos, lost_text_style).draw(display)?;
+
+    // an exclamation mark cut out of a filled triangle
+    let c = spec.center + Point::new(0, ICON_OFFSET_Y);
+    warning_sign(spec).into_styled(PrimitiveStyle::with_fill(theme.warning)).draw(display)?;
+    Line::new(c + Point::new(0, -3), c + Point::new(0, 2))
+        .into_styled(PrimitiveStyle::with_stroke(Rgb565::BLACK, 2))
+        .draw(display)?;
+    Rectangle::new(c + Point::new(-1, 4), Size::new(2, 2))
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+        .draw(display)
+}
+
+fn warning_sign(spec: &GaugeSpec) -> Triangle {
+    let c = spec.center + Point::new(0, ICON_OFFSET_Y);
+    Triangle::new(c + Point::new(0, -9), c + Point::new(-10, 8), c + Point::new(10, 8))
+}
+
+// Draws the whole gauge with the default theme
+pub fn render<Display>(display: &mut Display, spec: &GaugeSpec, speed: f32) -> Result<(), Display::Error>
+where
+    Display: DrawTarget<Color = Rgb565>,
+{
+    draw_speedometer(display, spec, &Theme::default(), speed)
+}
+
+
+// The static part of the gauge, drawn once and copied under every frame so an
+// update only has to draw the needle and the readout
+pub struct Dial {
+    spec: GaugeSpec,
+    theme: Theme,
+    background: Framebuffer,
+}
+
+impl Dial {
+    pub fn new(spec: GaugeSpec, theme: Theme) -> Self {
+        let mut background = Framebuffer::new();
+        draw_dial(&mut background, &spec, &theme).ok();
+        Self { spec, theme, background }
+    }
+
+    // Uses a pre-rendered 240x240 raw RGB565 image (like assets/speedometer.raw) as
+    // the dial. `spec` still places the needle, so it has to match the image.
+    pub fn load(path: impl AsRef<Path>, spec: GaugeSpec, theme: Theme) -> Result<Self> {
+        Ok(Self { spec, theme, background: Framebuffer::load(path)? })
+    }
+
+    pub fn spec(&self) -> &GaugeSpec {
+        &self.spec
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn background(&self) -> &Framebuffer {
+        &self.background
+    }
+
+    // Replaces the whole frame with the dial, then draws the needle and readout for `speed`
+    pub fn draw<Display>(&self, display: &mut Display, speed: f32) -> Result<(), Display::Error>
+    where
+        Display: DrawTarget<Color = Rgb565>,
+    {
+        display.fill_contiguous(&self.background.bounding_box(), self.background.pixels().iter().copied())?;
+        draw_needle(display, &self.spec, &self.theme, speed)?;
+        draw_readout(display, &self.spec, &self.theme, speed)
+    }
+}
+
+impl Default for Dial {
+    fn default() -> Self {
+        Self::new(GaugeSpec::default(), Theme::default())
+    }
+}
+
+
+// Draws frames on top of a `Dial`, remembering where the needle, readout and
+// status went so the next frame only restores those areas from the dial. `draw`
+// hands back the areas that changed so the sink can flush just those.
+pub struct Renderer {
+    dial: Dial,
+    // areas covered by the needle and readout last frame, None before the first
+    drawn: Option<Vec<Rectangle>>,
+    status: Option<String>,
+    badge: Option<String>,
+    // no signal, see `draw_signal_lost`
+    lost: bool,
+}
+
+impl Renderer {
+    // The needle is covered by a few short boxes along its length rather than one
+    // big one, which keeps the dirty area small when it is diagonal
+    const NEEDLE_SEGMENTS: i32 = 6;
+
+    pub fn new(dial: Dial) -> Self {
+        Self { dial, drawn: None, status: None, badge: None, lost: false }
+    }
+
+    // Shown under the unit from the next frame on, until cleared with None
+    pub fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    // Shown above the hub from the next frame on, until cleared with None
+    pub fn set_badge(&mut self, badge: Option<String>) {
+        self.badge = badge;
+    }
+
+    // Draws the gauge as having no signal from the next frame on, until cleared
+    pub fn set_signal_lost(&mut self, lost: bool) {
+        self.lost = lost;
+    }
+
+    pub fn draw<Display>(&mut self, display: &mut Display, speed: f32) -> Result<Vec<Rectangle>, Display::Error>
+    where
+        Display: DrawTarget<Color = Rgb565>,
+    {
+        let current = self.areas(speed);
+
+        let dirty = match self.drawn.take() {
+            Some(previous) => {
+                let background = self.dial.background();
+                for area in &previous {
+                    let area = area.intersection(&background.bounding_box());
+                    display.fill_contiguous(&area, background.area(&area))?;
+                }
+                self.draw_speed(display, speed)?;
+
+                let mut dirty = previous;
+                dirty.extend_from_slice(&current);
+                dirty
+            },
+            None => {
+                let background = self.dial.background();
+                display.fill_contiguous(&background.bounding_box(), background.pixels().iter().copied())?;
+                self.draw_speed(display, speed)?;
+                vec![self.dial.background().bounding_box()]
+            },
+        };
+
+        self.drawn = Some(current);
+        Ok(dirty)
+    }
+
+    // Everything that goes on top of the dial
+    fn draw_speed<Display>(&self, display: &mut Display, speed: f32) -> Result<(), Display::Error>
+    where
+        Display: DrawTarget<Color = Rgb565>,
+    {
+        let (spec, theme) = (self.dial.spec(), self.dial.theme());
+        if self.lost {
+            draw_signal_lost(display, spec, theme, speed)?;
+        } else {
+            draw_needle(display, spec, theme, speed)?;
+            draw_readout(display, spec, theme, speed)?;
+        }
+        if let Some(status) = &self.status {
+            draw_status(display, self.dial.spec(), self.dial.theme(), status)?;
+        }
+        if let Some(badge) = &self.badge {
+            draw_badge(display, self.dial.spec(), self.dial.theme(), badge)?;
+        }
+        Ok(())
+    }
+
+    // Forget what is on screen, the next frame is drawn and flushed in full
+    pub fn invalidate(&mut self) {
+        self.drawn = None;
+    }
+
+    fn areas(&self, speed: f32) -> Vec<Rectangle> {
+        let (spec, theme) = (self.dial.spec(), self.dial.theme());
+        let needle = needle(spec, speed, theme.needle_style());
+        let (start, end) = (needle.primitive.start, needle.primitive.end);
+        let mut areas: Vec<Rectangle> = (0..Self::NEEDLE_SEGMENTS)
+            .map(|i| {
+                let from = start + (end - start) * i / Self::NEEDLE_SEGMENTS;
+                let to = start + (end - start) * (i + 1) / Self::NEEDLE_SEGMENTS;
+                // a pixel of margin covers rounding where the segments meet
+                Line::new(from, to).into_styled(needle.style).bounding_box().offset(1)
+            })
+            .collect();
+
+        if self.lost {
+            let lost_text_style = theme.lost_text_style();
+            let (text, text_pos) = readout_text(spec, "--".to_string(), &lost_text_style);
+            areas.push(Text::new(&text, text_pos, lost_text_style).bounding_box());
+            areas.push(warning_sign(spec).bounding_box());
+        } else {
+            let speed_text_style = theme.speed_text_style();
+            let (speed_text, text_pos) = readout(spec, speed, &speed_text_style);
+            areas.push(Text::new(&speed_text, text_pos, speed_text_style).bounding_box());
+        }
+
+        if let Some(status) = &self.status {
+            let status_text_style = theme.status_text_style();
+            let (status_text, text_pos) = status_line(spec, status, &status_text_style);
+            areas.push(Text::new(&status_text, text_pos, status_text_style).bounding_box());
+        }
+        if let Some(badge) = &self.badge {
+            let text_style = theme.text_style();
+            areas.push(Text::new(badge, badge_pos(spec, badge, &text_style), text_style).bounding_box());
+        }
+        areas
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use embedded_graphics::{draw_target::DrawTarget, pixelcolor::Rgb565, primitives::Rectangle};
+use serde::Deserialize;
+
+use crate::speed::Speed;
+
+// The seams between the gauge and whatever it runs on. The Pi wiring lives in
+// `rpi`, the desktop stand-in in `simulator`; an ESP32 port only needs its own
+// implementations of these.
+
+// Something the gauge can be drawn into and then pushed to a screen
+pub trait DisplaySink: DrawTarget<Color = Rgb565> {
+    // Blank the frame buffer before the next frame is drawn
+    fn clear_buffer(&mut self);
+
+    // Push the frame buffer out to the screen
+    fn flush(&mut self) -> Result<()>;
+
+    // Push only the given areas, for screens that can be updated in part.
+    // Anything else just sends the whole frame.
+    fn flush_regions(&mut self, regions: &[Rectangle]) -> Result<()> {
+        let _ = regions;
+        self.flush()
+    }
+}
+
+// Backlight brightness, 0 is off and 255 is full
+pub trait Backlight {
+    fn set_brightness(&mut self, brightness: u8) -> Result<()>;
+}
+
+// Where speed readings come from
+pub trait SpeedSource {
+    // Blocks until the next speed reading is available. A reading that cannot be
+    // used is an error, the caller keeps showing the last good one.
+    fn next_speed(&mut self) -> Result<Speed>;
+}
+
+// Edges from a wheel or reed sensor, GPIO interrupts on the Pi
+pub trait PulseInput {
+    // Blocks until the next pulse, or None once `timeout` has passed without one.
+    // Returns when the pulse happened, as near to the edge as can be told.
+    fn wait_for_pulse(&mut self, timeout: Duration) -> Result<Option<Instant>>;
+}
+
+// How far the picture is turned clockwise on the screen, for panels mounted at an angle
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u16")]
+pub enum Rotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl TryFrom<u16> for Rotation {
+    type Error = anyhow::Error;
+
+    fn try_from(degrees: u16) -> Result<Self> {
+        match degrees {
+            0 => Ok(Rotation::Deg0),
+            90 => Ok(Rotation::Deg90),
+            180 => Ok(Rotation::Deg180),
+            270 => Ok(Rotation::Deg270),
+            _ => Err(anyhow!("rotation has to be 0, 90, 180 or 270, got {}", degrees)),
+        }
+    }
+}
+
+impl FromStr for Rotation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let degrees = s.parse::<u16>().map_err(|_| anyhow!("rotation has to be 0, 90, 180 or 270, got {}", s))?;
+        Rotation::try_from(degrees)
+    }
+}
@@ -0,0 +1,29 @@
+pub mod animation;
+pub mod arbiter;
+pub mod config;
+pub mod filter;
+pub mod framebuffer;
+pub mod gauge;
+pub mod hal;
+pub mod link;
+pub mod nmea;
+pub mod obd;
+pub mod panel;
+#[cfg(feature = "rpi")]
+pub mod rpi;
+pub mod signal;
+pub mod simulator;
+pub mod source;
+pub mod spec;
+pub mod speed;
+pub mod theme;
+pub mod watchdog;
+
+pub use config::Config;
+pub use framebuffer::Framebuffer;
+pub use gauge::{draw_speedometer, Dial};
+pub use hal::{Backlight, DisplaySink, PulseInput, Rotation, SpeedSource};
+pub use panel::Panel;
+pub use spec::GaugeSpec;
+pub use speed::{Speed, SpeedUnit};
+pub use theme::Theme;
@@ -0,0 +1,149 @@
+// The framed protocol on the serial link to the ESP32. Everything in here only
+// needs `core`, so the encoder can be copied into the microcontroller firmware
+// as is and both ends share the exact format.
+//
+// A frame, multi byte fields big endian:
+//
+//   0     1  START, 0x7E
+//   1     1  message type
+//   2     1  payload length N, at most MAX_PAYLOAD
+//   3     N  payload
+//   3+N   2  CRC-16/CCITT-FALSE over type, length and payload
+//
+// Message types and their payloads:
+//
+//   0x01  telemetry, 8 bytes: speed f32, unit u8 (0 the gauge's, 1 mph,
+//         2 km/h, 3 m/s, 4 knots), rpm u16, flags u8 (FLAG_*)
+//   0x02  heartbeat, no payload. Keeps the link alive while nothing changes.
+//
+// START can turn up inside a frame as well, there is no escaping. After a bad
+// CRC the decoder moves one byte on and looks for the next START, so it falls
+// back in step within a frame or two of any corruption.
+
+use crate::speed::SpeedUnit;
+
+pub const START: u8 = 0x7E;
+pub const MAX_PAYLOAD: usize = 32;
+pub const MAX_FRAME: usize = 3 + MAX_PAYLOAD + 2;
+
+const TELEMETRY: u8 = 0x01;
+const HEARTBEAT: u8 = 0x02;
+
+// The sender has a speed to report. Without it the speed field is not to be trusted
+// (no GPS fix yet, wheel sensor unplugged).
+pub const FLAG_SPEED_VALID: u8 = 0x01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Message {
+    Telemetry { speed: f32, unit: Option<SpeedUnit>, rpm: u16, flags: u8 },
+    Heartbeat,
+}
+
+// Why the bytes at the front of the buffer are not a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameError {
+    // the first byte is not START
+    NoStart,
+    Length(u8),
+    Crc { expected: u16, got: u16 },
+    UnknownType(u8),
+    Payload,
+}
+
+// CRC-16/CCITT-FALSE: polynomial 0x1021, initial 0xFFFF, no reflection
+pub fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in bytes {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+// The same codes go into the UDP speed packet
+pub(crate) fn unit_code(unit: Option<SpeedUnit>) -> u8 {
+    match unit {
+        None => 0,
+        Some(SpeedUnit::Mph) => 1,
+        Some(SpeedUnit::Kmh) => 2,
+        Some(SpeedUnit::MetersPerSecond) => 3,
+        Some(SpeedUnit::Knots) => 4,
+    }
+}
+
+pub(crate) fn unit_from_code(code: u8) -> Option<Option<SpeedUnit>> {
+    match code {
+        0 => Some(None),
+        1 => Some(Some(SpeedUnit::Mph)),
+        2 => Some(Some(SpeedUnit::Kmh)),
+        3 => Some(Some(SpeedUnit::MetersPerSecond)),
+        4 => Some(Some(SpeedUnit::Knots)),
+        _ => None,
+    }
+}
+
+// The reference encoder. Writes one frame into `out` and returns its length.
+pub fn encode(message: &Message, out: &mut [u8; MAX_FRAME]) -> usize {
+    let mut payload = [0u8; MAX_PAYLOAD];
+    let (kind, len) = match *message {
+        Message::Telemetry { speed, unit, rpm, flags } => {
+            payload[0..4].copy_from_slice(&speed.to_be_bytes());
+            payload[4] = unit_code(unit);
+            payload[5..7].copy_from_slice(&rpm.to_be_bytes());
+            payload[7] = flags;
+            (TELEMETRY, 8)
+        },
+        Message::Heartbeat => (HEARTBEAT, 0),
+    };
+
+    out[0] = START;
+    out[1] = kind;
+    out[2] = len as u8;
+    out[3..3 + len].copy_from_slice(&payload[..len]);
+    let crc = crc16(&out[1..3 + len]);
+    out[3 + len..5 + len].copy_from_slice(&crc.to_be_bytes());
+    5 + len
+}
+
+// Tries to take a frame off the front of `buf`. Ok(None) means the frame is not
+// all there yet; Ok(Some) and Err both come with how many bytes were used up,
+// on an error that is the one byte to skip before looking again.
+pub fn decode(buf: &[u8]) -> Result<Option<(Message, usize)>, (FrameError, usize)> {
+    match buf.first() {
+        None => return Ok(None),
+        Some(&START) => {},
+        Some(_) => return Err((FrameError::NoStart, 1)),
+    }
+    if buf.len() < 3 {
+        return Ok(None);
+    }
+    let (kind, len) = (buf[1], buf[2] as usize);
+    if len > MAX_PAYLOAD {
+        return Err((FrameError::Length(buf[2]), 1));
+    }
+    if buf.len() < 5 + len {
+        return Ok(None);
+    }
+
+    let got = u16::from_be_bytes([buf[3 + len], buf[4 + len]]);
+    let expected = crc16(&buf[1..3 + len]);
+    if got != expected {
+        return Err((FrameError::Crc { expected, got }, 1));
+    }
+
+    let payload = &buf[3..3 + len];
+    let message = match (kind, len) {
+        (TELEMETRY, 8) => Message::Telemetry {
+            speed: f32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]),
+            unit: unit_from_code(payload[4]).ok_or((FrameError::Payload, 1))?,
+            rpm: u16::from_be_bytes([payload[5], payload[6]]),
+            flags: payload[7],
+        },
+        (HEARTBEAT, 0) => Message::Heartbeat,
+        (TELEMETRY | HEARTBEAT, _) => return Err((FrameError::Payload, 1)),
+        (kind, _) => return Err((FrameError::UnknownType(kind), 1)),
+    };
+    Ok(Some((message, 5 + len)))
+}
@@ -1,299 +1,656 @@
-use std::{time::{Duration, Instant}, fs, env};
-use notify::{RecommendedWatcher, Watcher, RecursiveMode, Config, event::{DataChange, ModifyKind}, EventKind};
-use std::sync::mpsc::channel;
-
-use embedded_graphics::{
-    draw_target::DrawTarget,
-    mono_font::{ascii::{FONT_10X20, FONT_6X13_BOLD}, MonoTextStyle},
-    pixelcolor::Rgb565,
-    prelude::*,
-    primitives::{Circle, Line, PrimitiveStyle},
-    text::Text,
+use std::{
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
 };
-use profont::PROFONT_24_POINT;
-use gc9a01::{
-    display::DisplayResolution240x240,
-    mode::{BufferedGraphics, DisplayConfiguration},
-    prelude::SPIInterface,
-    rotation::DisplayRotation,
-    Gc9a01, SPIDisplayInterface,
-};
-use rppal::{
-    gpio::{Gpio, OutputPin},
-    hal::Delay,
-    pwm::{self, Pwm},
-    spi::*,
+
+use anyhow::{Context, Result};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use log::{debug, error, info, warn, LevelFilter};
+use speedometer::{
+    animation::Needle,
+    arbiter::Arbiter,
+    config::{ConfigWatcher, DisplayConfig, InputConfig, SourceKind},
+    filter::FilterChain,
+    gauge::Renderer,
+    simulator::{SimulatedBacklight, Simulator},
+    source::{
+        CanConfig, Ended, FileSource, GpsdSource, HttpSource, LineSource, MqttSource, NmeaSource, ObdSource, PulseConfig,
+        SerialSource, Sweep, UdpSource, UnixSource,
+    },
+    watchdog::Watchdog,
+    Backlight, Config, Dial, DisplaySink, Framebuffer, Rotation, Speed, SpeedSource, SpeedUnit,
 };
 
-use anyhow::{anyhow, Result};
 
+#[derive(Parser)]
+#[command(version, about = "Speedometer gauge for a round GC9A01 panel")]
+struct Cli {
+    /// TOML config file, see speedometer.toml [default: ./speedometer.toml if it exists]
+    #[arg(long, global = true, env = "SPEEDOMETER_CONFIG")]
+    config: Option<PathBuf>,
 
-fn set_brightness(bl: &mut Pwm, brightness: u8) -> Result<(), anyhow::Error> {
-    let pulse_width = match brightness {
-        0 => Duration::from_micros(0),
-        _ => Duration::from_micros((brightness as u64 * 3_000) / 255),
-    };
-    match bl.set_pulse_width(pulse_width) {
-        Ok(_) => Ok(()),
-        Err(e) => Err(anyhow!("Error setting pulse width: {}", e)),
+    /// Where the frames go
+    #[arg(long, global = true, value_enum, default_value_t = Backend::default())]
+    backend: Backend,
+
+    /// Directory the simulator writes speedometer.ppm to
+    #[arg(long, global = true, default_value = "./frames")]
+    out_dir: PathBuf,
+
+    #[command(flatten)]
+    overrides: Overrides,
+
+    /// More log output, -vv for everything
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Less log output, -qq for errors only
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "verbose")]
+    quiet: u8,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+// Settings that win over the config file, also after it is reloaded
+#[derive(Args, Clone)]
+struct Overrides {
+    /// Where the speed comes from, see input.source in speedometer.toml [default: input.source from the config]
+    #[arg(long, global = true)]
+    source: Option<SourceKind>,
+
+    /// Read a speed sample per line from stdin, same as --source stdin
+    #[arg(long, global = true, conflicts_with = "source")]
+    stdin: bool,
+
+    /// File to read the speed from [default: input.path from the config]
+    #[arg(long, global = true)]
+    input: Option<PathBuf>,
+
+    /// Address to listen for UDP speed datagrams on [default: input.udp.bind from the config]
+    #[arg(long, global = true)]
+    listen: Option<String>,
+
+    /// Serial port the ESP32 is on [default: input.serial.port from the config]
+    #[arg(long, global = true)]
+    serial_port: Option<String>,
+
+    /// GPS device, NMEA log or - for stdin [default: input.nmea.device from the config]
+    #[arg(long, global = true)]
+    nmea_device: Option<String>,
+
+    /// host:port of gpsd [default: input.gpsd.address from the config]
+    #[arg(long, global = true)]
+    gpsd: Option<String>,
+
+    /// OBD-II adapter, a serial device or host:port [default: input.obd.device from the config]
+    #[arg(long, global = true)]
+    obd_device: Option<String>,
+
+    /// SocketCAN interface, e.g. vcan0 [default: input.can.interface from the config]
+    #[arg(long, global = true)]
+    can_interface: Option<String>,
+
+    /// Unix socket to take speed samples on [default: input.unix.path from the config]
+    #[arg(long, global = true)]
+    socket: Option<PathBuf>,
+
+    /// host:port of the MQTT broker [default: input.mqtt.broker from the config]
+    #[arg(long, global = true)]
+    mqtt_broker: Option<String>,
+
+    /// MQTT topic the speed is published on [default: input.mqtt.topic from the config]
+    #[arg(long, global = true)]
+    mqtt_topic: Option<String>,
+
+    /// Address the HTTP server listens on [default: input.http.bind from the config]
+    #[arg(long, global = true)]
+    http: Option<String>,
+
+    /// Unit shown under the readout, e.g. km/h
+    #[arg(long, global = true)]
+    unit: Option<String>,
+
+    /// Backlight level, 0 (off) to 255
+    #[arg(long, global = true)]
+    brightness: Option<u8>,
+
+    /// Clockwise rotation of the panel: 0, 90, 180 or 270
+    #[arg(long, global = true)]
+    rotation: Option<Rotation>,
+}
+
+impl Overrides {
+    fn apply(&self, config: &mut Config) {
+        // one source given here replaces the failover list
+        if let Some(source) = self.source {
+            config.input.source = source;
+            config.input.failover.clear();
+        }
+        if self.stdin {
+            config.input.source = SourceKind::Stdin;
+            config.input.failover.clear();
+        }
+        if let Some(input) = &self.input {
+            config.input.path = input.clone();
+        }
+        if let Some(listen) = &self.listen {
+            config.input.udp.bind = listen.clone();
+        }
+        if let Some(port) = &self.serial_port {
+            config.input.serial.port = port.clone();
+        }
+        if let Some(device) = &self.nmea_device {
+            config.input.nmea.device = device.clone();
+        }
+        if let Some(address) = &self.gpsd {
+            config.input.gpsd.address = address.clone();
+        }
+        if let Some(device) = &self.obd_device {
+            config.input.obd.device = device.clone();
+        }
+        if let Some(interface) = &self.can_interface {
+            config.input.can.interface = interface.clone();
+        }
+        if let Some(socket) = &self.socket {
+            config.input.unix.path = socket.clone();
+        }
+        if let Some(broker) = &self.mqtt_broker {
+            config.input.mqtt.broker = broker.clone();
+        }
+        if let Some(topic) = &self.mqtt_topic {
+            config.input.mqtt.topic = topic.clone();
+        }
+        if let Some(bind) = &self.http {
+            config.input.http.bind = bind.clone();
+        }
+        if let Some(unit) = &self.unit {
+            config.gauge.unit = unit.clone();
+        }
+        if let Some(brightness) = self.brightness {
+            config.display.brightness = brightness;
+        }
+        if let Some(rotation) = self.rotation {
+            config.display.rotation = rotation;
+        }
     }
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Follow the input and keep the gauge up to date (the default)
+    Run,
+    /// Draw a single frame to an image (.png, .ppm or .raw) and exit
+    Render {
+        /// Speed the needle points at
+        #[arg(long)]
+        speed: f32,
+        /// Image to write
+        #[arg(long, default_value = "frame.png")]
+        out: PathBuf,
+    },
+    /// Sweep the needle over the scale and start over, like update_speed.sh
+    Demo {
+        /// Speed added per reading
+        #[arg(long, default_value_t = 1.0, value_parser = positive)]
+        step: f32,
+        /// Time between readings
+        #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u64).range(1..))]
+        interval_ms: u64,
+    },
+}
 
-fn draw_speedometer<Display>(
-    display: &mut Display, 
-    speed: f32,
-    circle: Circle,
-    circle_style: PrimitiveStyle<Rgb565>,
-    text_style: MonoTextStyle<'_, Rgb565>, 
-    speed_text_style: MonoTextStyle<'_, Rgb565>, 
-    unit_text_style: MonoTextStyle<'_, Rgb565>
-) -> Result<(), Display::Error>
-where
-    Display: DrawTarget<Color = Rgb565>,
-{
-    // let function_now = Instant::now();
-    // let mut now = Instant::now();
-
-    // Constants and precomputed values
-    const PI: f32 = std::f32::consts::PI;
-    const TICK_LENGTH: i32 = 20;
-    const DEFAULT_TEXT_RADIUS: i32 = 15;
-    const NEEDLE_LENGTH: u8 = 92; // 112 - 20
-    const TEXT_OFFSET_Y: i32 = 40;
-    const UNIT_TEXT: &str = "mi/hr";
-    const UNIT_TEXT_POS: Point = Point::new(95, 179);
-    const START_ANGLE: f32 = std::f32::consts::PI;
-    const CENTER: Point = Point::new(119, 119);
-    const RADIUS: i32 = 112;
-    const DIAMETER: u32 = RADIUS as u32 * 2;
-    const TOP_LEFT: Point = Point::new(8, 8);
-
-    // let mut elapsed = now.elapsed();
-    // println!("Precompute Elapsed: {:?}", elapsed);
-
-    // now = Instant::now();
-
-    // Draw the dial
-    circle.into_styled(circle_style).draw(display)?;
-
-    // elapsed = now.elapsed();
-    // println!("Circle Elapsed: {:?}", elapsed);
-
-    // now = Instant::now();
-
-    for i in 0..=12 {
-        let angle = (i as f32 * 2.0 * PI / 24.0) + START_ANGLE;
-        let outer_end = CENTER + Point::new(
-            (angle.cos() * RADIUS as f32) as i32,
-            (angle.sin() * RADIUS as f32) as i32,
-        );
-        let inner_end = CENTER + Point::new(
-            (angle.cos() * (RADIUS - TICK_LENGTH) as f32) as i32,
-            (angle.sin() * (RADIUS - TICK_LENGTH) as f32) as i32,
-        );
-
-        Line::new(outer_end, inner_end)
-            .into_styled(PrimitiveStyle::with_stroke(Rgb565::new(0, 191, 83), if i % 2 == 0 { 3 } else { 1 }))
-            .draw(display)?;
-        
-        if i % 2 == 0 {
-            let number = i * 10;
-            let number_width = match number {
-                1..=9 => 6,
-                10..=99 => 12,
-                _ => 18,
-            };
-            let text_offset = Point::new((number_width / 2) as i32, 7); // Half of 13 (height)
-            let additional_offset = Point::new(1, 9);
-            let text_angle = angle + START_ANGLE;
-            let text_pos = CENTER - Point::new(
-                (text_angle.cos() * (RADIUS - (DEFAULT_TEXT_RADIUS + TICK_LENGTH)) as f32) as i32, 
-                (text_angle.sin() * (RADIUS - (DEFAULT_TEXT_RADIUS + TICK_LENGTH)) as f32) as i32
-            ) - text_offset + additional_offset;
-            Text::new(&format!("{:2}", number), text_pos, text_style).draw(display)?;
+// A demo that steps by nothing never moves the needle
+fn positive(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v),
+        Ok(_) => Err("has to be above 0".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Backend {
+    /// The GC9A01 on the Pi's SPI bus
+    Hardware,
+    /// Frames written to --out-dir as PPM
+    Simulator,
+    /// Drawn in memory only, for timing and testing inputs
+    Headless,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        if cfg!(feature = "rpi") {
+            Backend::Hardware
+        } else {
+            Backend::Simulator
         }
     }
+}
+
 
-    // elapsed = now.elapsed();
-    // println!("Tick Elapsed: {:?}", elapsed);
+// Everything the main loop waits on, from the speed sources and the config
+// watcher. Sources are numbered in order of preference.
+enum Update {
+    Speed(usize, Result<Speed>),
+    // the source has nothing more to give, piped stdin was closed
+    Ended(usize),
+    // boxed, a config is a lot bigger than a speed
+    Config(Result<Box<Config>>),
+}
 
-    
+// how often the sources are checked for going stale
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+fn run<D, B>(
+    display: &mut D,
+    backlight: &mut B,
+    dial: Dial,
+    mut config: Config,
+    mut arbiter: Arbiter,
+    updates: Receiver<Update>,
+) where
+    D: DisplaySink,
+    B: Backlight,
+{
+    // the ring, ticks and numbers never change, so draw them once
+    let mut renderer = Renderer::new(dial);
+    // the needle and readout catch up with the speed frame by frame
+    let mut needle = Needle::new(&config.animation, config.gauge.min, config.gauge.max);
+    let mut filters = FilterChain::new(&config.filter);
+    // what is wrong with the input, shown on the dial until the next good reading
+    let mut status = None;
+    let timeout = Some(Duration::from_millis(config.input.signal_timeout_ms)).filter(|t| !t.is_zero());
+    let mut watchdog = Watchdog::new(timeout, Instant::now());
+    // something other than the needle changed since the last frame
+    let mut dirty = false;
+    let mut ended = false;
+    let mut last_frame = Instant::now();
+
+    loop {
+        // wait for the next frame while there is something to draw, readings
+        // coming in meanwhile only move where the needle is headed
+        let resting = !(dirty || needle.moving());
+        let wait = match resting {
+            false => config.animation.frame_interval().saturating_sub(last_frame.elapsed()),
+            true => CHECK_INTERVAL,
+        };
+        let update = updates.recv_timeout(wait);
+        // when the update came in, which is what staleness and the filters go by
+        let now = Instant::now();
+        // what the gauge should show now, from whichever source the arbiter picks
+        let shown = match update {
+            Ok(Update::Speed(index, reading)) => arbiter.reading(index, reading, now),
+            Ok(Update::Ended(index)) => {
+                let shown = arbiter.ended(index, now);
+                if arbiter.all_ended() {
+                    info!("Input ended");
+                    // the last frame keeps the last speed
+                    ended = true;
+                    None
+                } else {
+                    shown
+                }
+            },
+            Ok(Update::Config(Ok(new))) => {
+                // a background that does not load keeps the old config, like one that does not parse
+                let dial = match dial_for(&new) {
+                    Ok(dial) => dial,
+                    Err(e) => {
+                        error!("{:#}", e);
+                        continue;
+                    },
+                };
+                if config.needs_restart(&new) {
+                    warn!("Display and input settings only take effect after a restart");
+                }
+                if let Err(e) = backlight.set_brightness(new.display.brightness) {
+                    error!("{:#}", e);
+                }
+                // a new dial, the next frame is drawn in full
+                renderer = Renderer::new(dial);
+                needle.set_config(&new.animation);
+                needle.set_scale(new.gauge.min, new.gauge.max);
+                if new.filter != config.filter {
+                    filters = FilterChain::new(&new.filter);
+                }
+                // the rest of the input and display keep running as they are until the restart
+                config = Config {
+                    display: DisplayConfig { brightness: new.display.brightness, ..config.display },
+                    input: config.input,
+                    ..*new
+                };
+                info!("Reloaded config");
+                dirty = true;
+                None
+            },
+            Ok(Update::Config(Err(e))) => {
+                error!("{:#}", e);
+                None
+            },
+            Err(RecvTimeoutError::Timeout) => arbiter.check(now),
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        if let Some(reading) = shown {
+            dirty = true;
+            let gauge_unit = config.gauge.unit.parse::<SpeedUnit>().ok();
+            match reading.and_then(|r| r.in_unit(gauge_unit)) {
+                Ok(raw) => {
+                    let value = filters.apply(raw, now);
+                    debug!("speed {} filtered to {}", raw, value);
+                    let (min, max) = (config.gauge.min, config.gauge.max);
+                    status = match value {
+                        v if v > max => Some(format!("over range: {}", v)),
+                        v if v < min => Some(format!("under range: {}", v)),
+                        _ => None,
+                    };
+                    if let Some(status) = &status {
+                        warn!("{}", status);
+                    }
+                    // the needle stops at the ends of the scale
+                    needle.set_target(value.clamp(min, max));
+                    watchdog.feed(now);
+                },
+                Err(e) => {
+                    // keep showing the last good speed
+                    error!("Bad speed reading: {:#}", e);
+                    status = Some(e.to_string());
+                },
+            }
+        }
 
-    // now = Instant::now();
+        if watchdog.check(now) {
+            dirty = true;
+            if watchdog.lost() {
+                warn!("No speed for {} ms, signal lost", config.input.signal_timeout_ms);
+            } else {
+                info!("Signal back");
+            }
+        }
+        if ended {
+            // nothing more is coming, so the last frame shows where the needle ends up
+            needle.settle();
+        } else if !(dirty || needle.moving()) || last_frame.elapsed() < config.animation.frame_interval() {
+            continue;
+        }
+        let now = Instant::now();
+        // after a rest the needle sets off with this frame, rather than making up
+        // for the time it stood still
+        needle.advance(if resting { config.animation.frame_interval() } else { now - last_frame });
+        last_frame = now;
+        dirty = false;
+
+        // update the display, sending only what moved
+        renderer.set_status(status.clone());
+        renderer.set_badge(arbiter.badge());
+        renderer.set_signal_lost(watchdog.lost());
+        if let Ok(regions) = renderer.draw(display, needle.position()) {
+            if let Err(e) = display.flush_regions(&regions) {
+                error!("{:#}", e);
+                // we no longer know what is on the panel
+                renderer.invalidate();
+            }
+        }
+        if ended {
+            break;
+        }
+    }
+}
 
-    // // Calculate needle position based on speed
-    let angle = speed_to_angle(speed, START_ANGLE);
-    let needle_end = CENTER + Point::new(
-        (angle.cos() * NEEDLE_LENGTH as f32) as i32,
-        (angle.sin() * NEEDLE_LENGTH as f32) as i32,
-    );
 
-    Line::new(CENTER, needle_end)
-        .into_styled(PrimitiveStyle::with_stroke(Rgb565::RED, 2))
-        .draw(display)?;
+#[cfg(feature = "rpi")]
+fn run_hardware(config: Config, arbiter: Arbiter, updates: Receiver<Update>) -> Result<()> {
+    use speedometer::rpi;
 
-    // elapsed = now.elapsed();
-    // println!("Needle Elapsed: {:?}", elapsed);
+    let dial = dial_for(&config)?;
+    let (mut display, mut backlight) = rpi::init(&config.display).context("Unable to set up the display")?;
+    backlight.set_brightness(config.display.brightness).context("Unable to set brightness")?;
 
-    // now = Instant::now();
+    run(&mut display.panel, &mut backlight, dial, config, arbiter, updates);
+    Ok(())
+}
 
-    // Display speed as text
-    let speed_text = format!("{}", speed);
-    let speed_text_width = match speed_text.len() {
-        1 => 16,
-        2 => 32,
-        _ => 48,
-    };
-    let text_offset = Point::new((speed_text_width / 2) as i32, (speed_text_style.font.character_size.height / 2) as i32);
-    let text_pos = CENTER - text_offset + Point::new(1, TEXT_OFFSET_Y);
-    
-    Text::new(&speed_text, text_pos, speed_text_style).draw(display)?;
+#[cfg(not(feature = "rpi"))]
+fn run_hardware(_config: Config, _arbiter: Arbiter, _updates: Receiver<Update>) -> Result<()> {
+    Err(anyhow::anyhow!("Built without the `rpi` feature, use --backend simulator or headless"))
+}
 
-    // Display unit as text
-    Text::new(UNIT_TEXT, UNIT_TEXT_POS, unit_text_style).draw(display)?;
 
-    // elapsed = now.elapsed();
-    // println!("Text Elapsed: {:?}", elapsed);
+fn run_simulator(config: Config, arbiter: Arbiter, updates: Receiver<Update>, out_dir: &Path) -> Result<()> {
+    let mut display = Simulator::new(out_dir);
+    display.set_rotation(config.display.rotation);
+    info!("Simulating display, writing frames to {}", out_dir.display());
 
-    // elapsed = function_now.elapsed();
-    // println!("Function Elapsed: {:?}", elapsed);
+    // draw once up front so there is something to look at before the first update
+    let dial = dial_for(&config)?;
+    dial.draw(&mut display, 0.0).ok();
+    display.flush().context("Unable to write simulator frame")?;
 
+    let mut backlight = SimulatedBacklight::default();
+    backlight.set_brightness(config.display.brightness)?;
+    run(&mut display, &mut backlight, dial, config, arbiter, updates);
     Ok(())
 }
 
-fn speed_to_angle(speed: f32, start_angle: f32) -> f32 {
-    ((8.0 * speed) / 960.0) * std::f32::consts::PI + start_angle
+
+// Runs the source on its own thread so config reloads are not stuck behind it
+fn spawn_source<S>(mut source: S, index: usize, tx: Sender<Update>)
+where
+    S: SpeedSource + Send + 'static,
+{
+    thread::spawn(move || loop {
+        let update = match source.next_speed() {
+            Err(e) if e.is::<Ended>() => Update::Ended(index),
+            reading => Update::Speed(index, reading),
+        };
+        let ended = matches!(update, Update::Ended(_));
+        if tx.send(update).is_err() || ended {
+            break;
+        }
+    });
 }
 
 
-fn main() {
-    // setup of the SPI
-    // Table of GC9A01 driver (https://www.waveshare.com/wiki/1.28inch_LCD_Module) to physical pinout to function to BCM pin (https://pinout.xyz/)
-    // GC9A01 | Pi | SPI      | BCM
-    //  DIN   | 19 | MOSI     | 10
-    //  CLK   | 23 | SCLK     | 11
-    let spi = Spi::new(Bus::Spi0, SlaveSelect::Ss0, 27_000_000, Mode::Mode0)
-        .expect("Error setting SPI preferences");
-
-    //setup the rest of the pins for Gc9a01 driver
-    // Note: Slave Select(SS) is also know as Chip Enable(CE) or Chip Select(CS)
-    // GC9A01 | Pi | BCM
-    //   CS   | 24 | 8 (CE0)
-    //   DC   | 22 | 25
-    //   RST  | 13 | 27
-    //   BL   | 12 | 18
-    let gpio = Gpio::new().expect("Could not set up GPIO");
-
-    // CS pin
-    let cs = gpio.get(8).expect("Unable to get pin 8 (CS)").into_output();
-    // Data or Command? pin (Set which mode to be in 0 for command, 1 for data)
-    let dc = gpio.get(25).expect("Unable to get pin 13").into_output();
-    // reset pin
-    let mut reset = gpio.get(27).expect("Unable to get pin 13").into_output();
-    // backlight pin
-    // The LEDPWM
-    // duty is calculated as DBV[7:0]/255 x period (affected by OSC frequency).
-    // For example: LEDPWM period = 3ms, and DBV[7:0] = ‘200DEC’. Then LEDPWM duty = 200 / 255=78.1%.
-    // Correspond to the LEDPWM period = 3 ms, the high-level of LEDPWM (high effective) = 2.344ms, and the
-    // low-level of LEDPWM = 0.656ms.
-    let period = Duration::from_micros(3_000);
-    let pulse_width = Duration::from_micros(3_000);
-
-    let mut bl = Pwm::with_period(
-        pwm::Channel::Pwm0,
-        period,
-        pulse_width,
-        pwm::Polarity::Normal,
-        true,
-    )
-    .expect("Unable to set up PWM");
-
-    // create the interface for the display
-    let interface = SPIDisplayInterface::new(spi, dc, cs);
-
-    let mut display_driver: Gc9a01<
-        SPIInterface<Spi, OutputPin, OutputPin>,
-        DisplayResolution240x240,
-        BufferedGraphics<DisplayResolution240x240>,
-    > = Gc9a01::new(
-        interface,
-        DisplayResolution240x240,
-        DisplayRotation::Rotate0,
-    )
-    .into_buffered_graphics();
-
-    let mut delay = Delay::new();
-
-    display_driver.reset(&mut reset, &mut delay).ok();
-    display_driver.init(&mut delay).ok();
-
-    set_brightness(&mut bl, 255).expect("Unable to set brightness");
-
-    // set speed to 0
-    let mut speed = 0.0;
-
-    const NEON_GREEN: Rgb565 = Rgb565::new(0, 191, 83);
-
-    let text_style = MonoTextStyle::new(&FONT_6X13_BOLD, Rgb565::WHITE);
-    let speed_text_style = MonoTextStyle::new(&PROFONT_24_POINT, Rgb565::WHITE);
-    let unit_text_style = MonoTextStyle::new(&FONT_10X20, Rgb565::WHITE);
-    let circle_style = PrimitiveStyle::with_stroke(NEON_GREEN, 4);
-    const RADIUS: i32 = 112;
-    const DIAMETER: u32 = RADIUS as u32 * 2;
-    const TOP_LEFT: Point = Point::new(8, 8);
-    const CIRCLE: Circle = Circle::new(TOP_LEFT, DIAMETER);
-
-    // Create a channel to receive the events.
-    let (tx, rx) = channel();
+// Starts every source in the input config, numbered as the arbiter has them. With
+// several, one that does not start is left out rather than stopping the rest.
+fn spawn_input(input: &InputConfig, tx: Sender<Update>) -> Result<()> {
+    let sources = input.sources();
+    for (index, failover) in sources.iter().enumerate() {
+        let started = spawn_kind(failover.source, index, input, tx.clone())
+            .with_context(|| format!("Unable to start the {} source", failover.source));
+        match started {
+            Ok(()) => {},
+            Err(e) if sources.len() > 1 => {
+                error!("{:#}", e);
+                tx.send(Update::Ended(index)).ok();
+            },
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+fn spawn_kind(kind: SourceKind, index: usize, input: &InputConfig, tx: Sender<Update>) -> Result<()> {
+    match kind {
+        SourceKind::File => {
+            let source = FileSource::with_mode(&input.path, input.watch)
+                .with_context(|| format!("Unable to watch {}", input.path.display()))?;
+            spawn_source(source, index, tx);
+        },
+        SourceKind::Udp => {
+            let source = UdpSource::bind(&input.udp)?;
+            info!("Listening for speed datagrams on {}", source.local_addr()?);
+            spawn_source(source, index, tx);
+        },
+        SourceKind::Serial => {
+            let source = SerialSource::open(&input.serial)?;
+            info!("Reading speed frames from {} at {} baud", input.serial.port, input.serial.baud);
+            spawn_source(source, index, tx);
+        },
+        SourceKind::Nmea => {
+            let source = NmeaSource::open(&input.nmea)?;
+            info!("Reading NMEA sentences from {}", input.nmea.device);
+            spawn_source(source, index, tx);
+        },
+        SourceKind::Gpsd => {
+            info!("Reading the speed from gpsd at {}", input.gpsd.address);
+            spawn_source(GpsdSource::new(&input.gpsd), index, tx);
+        },
+        SourceKind::Obd => {
+            info!("Polling the speed from the OBD adapter on {}", input.obd.device);
+            spawn_source(ObdSource::new(&input.obd), index, tx);
+        },
+        SourceKind::Can => spawn_can(&input.can, index, tx)?,
+        SourceKind::Pulse => spawn_pulse(&input.pulse, index, tx)?,
+        SourceKind::Stdin => spawn_source(LineSource::new(BufReader::new(io::stdin())), index, tx),
+        SourceKind::Unix => {
+            let source = UnixSource::bind(&input.unix)?;
+            info!("Taking speed samples on {}", source.path().display());
+            spawn_source(source, index, tx);
+        },
+        SourceKind::Mqtt => {
+            info!("Reading the speed from {} on MQTT broker {}", input.mqtt.topic, input.mqtt.broker);
+            spawn_source(MqttSource::new(&input.mqtt)?, index, tx);
+        },
+        SourceKind::Http => {
+            let source = HttpSource::bind(&input.http)?;
+            let addr = source.local_addr()?;
+            info!("Taking speed samples on http://{}/speed and ws://{}/ws", addr, addr);
+            spawn_source(source, index, tx);
+        },
+    }
+    Ok(())
+}
 
-    let path = std::path::Path::new("./data/speed.txt");
+#[cfg(feature = "can")]
+fn spawn_can(can: &CanConfig, index: usize, tx: Sender<Update>) -> Result<()> {
+    let source = speedometer::source::CanSource::open(can)?;
+    info!("Reading {} from CAN interface {}", can.speed_signal, can.interface);
+    spawn_source(source, index, tx);
+    Ok(())
+}
 
-    // Create a watcher object, delivering debounced events.
-    // The Duration::from_secs(10) is the debounce period.
-    let mut watcher = RecommendedWatcher::new(tx, Config::default()).unwrap();
+#[cfg(not(feature = "can"))]
+fn spawn_can(_can: &CanConfig, _index: usize, _tx: Sender<Update>) -> Result<()> {
+    Err(anyhow::anyhow!("Built without the `can` feature, rebuild with --features can"))
+}
 
-    // Add a path to be watched. All files and directories at that path and
-    // below will be monitored for changes.
-    watcher.watch(&path, RecursiveMode::NonRecursive).unwrap();
+#[cfg(feature = "rpi")]
+fn spawn_pulse(pulse: &PulseConfig, index: usize, tx: Sender<Update>) -> Result<()> {
+    use speedometer::{rpi::GpioPulses, source::PulseSource};
 
-    loop {
-        match rx.recv() {
-            Ok(result) => match result {
-                Ok(event) => match event.kind {
-                    EventKind::Modify(ModifyKind::Data(DataChange::Any)) => {
-                        // println!("data changed");
-                        // read the file and update the speed
-                        // set speed to 0
-                        match fs::read_to_string("./data/speed.txt") {
-                            Ok(s) => {
-                                // Check if the string is empty
-                                if s.is_empty() {
-                                    // println!("Speed is empty");
-                                    continue;
-                                }
-
-                                // println!("Speed: {}", s);
-                                speed = s.trim().parse::<f32>().unwrap();
-                                // update the display
-                                display_driver.clear();
-                                draw_speedometer(&mut display_driver, speed, CIRCLE, circle_style, text_style, speed_text_style, unit_text_style).ok();
-                                display_driver.flush().ok();
-                                // let elapsed = now.elapsed();
-                            },
-                            Err(e) => println!("Error reading file: {:?}", e),
-                        }
+    let input = GpioPulses::open(pulse)?;
+    info!("Counting wheel pulses on GPIO {}", pulse.pin);
+    spawn_source(PulseSource::new(input, pulse), index, tx);
+    Ok(())
+}
 
-                    },
-                    _ => continue,
-                },
+#[cfg(not(feature = "rpi"))]
+fn spawn_pulse(_pulse: &PulseConfig, _index: usize, _tx: Sender<Update>) -> Result<()> {
+    Err(anyhow::anyhow!("Built without the `rpi` feature, there is no GPIO to count pulses on"))
+}
 
-                Err(e) => println!("watch error: {:?}", e),
 
-            },
-            Err(e) => println!("watch error: {:?}", e),
+fn load_config(path: Option<&Path>, overrides: &Overrides) -> Result<Config> {
+    let mut config = match path {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    overrides.apply(&mut config);
+    config.validate()?;
+    Ok(config)
+}
+
+// The dial from [gauge] background when there is one, drawn from the spec otherwise
+fn dial_for(config: &Config) -> Result<Dial> {
+    match &config.gauge.background {
+        Some(path) => Dial::load(path, config.gauge.clone(), config.theme).context("Unable to load the dial background"),
+        None => Ok(Dial::new(config.gauge.clone(), config.theme)),
     }
 }
 
+fn render(config: &Config, speed: f32, out: &Path) -> Result<()> {
+    let mut frame = Framebuffer::new();
+    dial_for(config)?.draw(&mut frame, speed).ok();
+    frame.rotated(config.display.rotation).save(out)?;
+    info!("Wrote {}", out.display());
+    Ok(())
 }
 
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    let level = match i16::from(cli.verbose) - i16::from(cli.quiet) {
+        ..=-2 => LevelFilter::Error,
+        -1 => LevelFilter::Warn,
+        0 => LevelFilter::Info,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    env_logger::Builder::new().filter_level(level).format_timestamp_millis().init();
+
+    // without --config or SPEEDOMETER_CONFIG, use ./speedometer.toml when there is one
+    let config_path = cli.config.clone().or_else(|| Some(PathBuf::from("./speedometer.toml")).filter(|p| p.exists()));
+    let config = load_config(config_path.as_deref(), &cli.overrides)?;
+    if let Some(path) = &config_path {
+        debug!("Using config {}", path.display());
+    }
+
+    let (tx, rx) = channel();
+
+    let arbiter = match cli.command.unwrap_or(Command::Run) {
+        Command::Render { speed, out } => return render(&config, speed, &out),
+        Command::Demo { step, interval_ms } => {
+            // a step too small for the needle to show would never get anywhere either
+            let least = (config.gauge.max - config.gauge.min) * 1e-6;
+            if step < least {
+                return Err(anyhow::anyhow!("--step {} is too small to move the needle, at least {}", step, least));
+            }
+            let sweep = Sweep::new(config.gauge.min, config.gauge.max, step, Duration::from_millis(interval_ms));
+            spawn_source(sweep, 0, tx.clone());
+            Arbiter::single(config.input.source)
+        },
+        Command::Run => {
+            spawn_input(&config.input, tx.clone())?;
+            Arbiter::new(&config.input.sources())
+        },
+    };
+
+    // visual settings are reloaded when the config is saved
+    let _watcher = match config_path {
+        Some(path) => {
+            let tx = tx.clone();
+            let overrides = cli.overrides.clone();
+            let watcher = ConfigWatcher::new(path, move |config| {
+                // the command line can turn a good file into a bad config, the old one stays then
+                let config = config.and_then(|mut config| {
+                    overrides.apply(&mut config);
+                    config.validate().context("Invalid config with the command line settings")?;
+                    Ok(Box::new(config))
+                });
+                tx.send(Update::Config(config)).ok();
+            })
+            .context("Unable to watch the config")?;
+            Some(watcher)
+        },
+        None => None,
+    };
+
+    match cli.backend {
+        Backend::Hardware => run_hardware(config, arbiter, rx),
+        Backend::Simulator => run_simulator(config, arbiter, rx, &cli.out_dir),
+        Backend::Headless => {
+            let dial = dial_for(&config)?;
+            let mut backlight = SimulatedBacklight::default();
+            backlight.set_brightness(config.display.brightness)?;
+            run(&mut Framebuffer::new(), &mut backlight, dial, config, arbiter, rx);
+            Ok(())
+        },
+    }
+}
@@ -0,0 +1,71 @@
+// The parts of NMEA 0183 a GPS receiver gives us the speed with. Sentences look
+// like `$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A`:
+// a talker (GP, GN, GL, ...), a type, comma separated fields and an XOR
+// checksum of everything between `$` and `*`.
+
+use anyhow::{anyhow, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sentence {
+    // recommended minimum: whether the fix is good, speed over ground in knots
+    // and the course over ground in degrees true
+    Rmc { valid: bool, speed_knots: Option<f32>, course: Option<f32> },
+    // track and ground speed. None when the receiver says the data is not valid.
+    Vtg { speed_knots: Option<f32>, speed_kmh: Option<f32>, course: Option<f32>, valid: bool },
+    // fix data: quality 0 is no fix, 1 GPS, 2 DGPS and so on
+    Gga { quality: u8, satellites: Option<u8> },
+}
+
+fn checksum(body: &str) -> u8 {
+    body.bytes().fold(0, |sum, b| sum ^ b)
+}
+
+// Wraps a sentence body in `$` and its checksum, for senders and tests
+pub fn sentence(body: &str) -> String {
+    format!("${}*{:02X}", body, checksum(body))
+}
+
+fn number<T: std::str::FromStr>(field: Option<&str>) -> Option<T> {
+    field.filter(|f| !f.is_empty())?.parse().ok()
+}
+
+// Parses one line. Ok(None) for sentences other than RMC, VTG and GGA.
+pub fn parse(line: &str) -> Result<Option<Sentence>> {
+    let line = line.trim_end();
+    let rest = line.strip_prefix('$').ok_or_else(|| anyhow!("not an NMEA sentence"))?;
+    let (body, sum) = rest.split_once('*').ok_or_else(|| anyhow!("NMEA sentence without a checksum"))?;
+    let sum = u8::from_str_radix(sum, 16).map_err(|_| anyhow!("bad NMEA checksum \"{}\"", sum))?;
+    if checksum(body) != sum {
+        return Err(anyhow!("NMEA checksum {:02X}, expected {:02X}", sum, checksum(body)));
+    }
+
+    let mut fields = body.split(',');
+    let kind = fields.next().unwrap_or_default();
+    // the talker is the first two letters, or just "P" for proprietary sentences.
+    // Line noise can leave other characters there, which is not a sentence either.
+    if kind.len() != 5 || !kind.is_ascii() {
+        return Ok(None);
+    }
+    let fields: Vec<&str> = fields.collect();
+    let field = |i: usize| fields.get(i).copied();
+
+    let sentence = match &kind[2..] {
+        // time, status, lat, N/S, lon, E/W, speed, course, date, ...
+        "RMC" => Sentence::Rmc {
+            valid: field(1) == Some("A"),
+            speed_knots: number(field(6)),
+            course: number(field(7)),
+        },
+        // course true, T, course magnetic, M, knots, N, km/h, K, mode (NMEA 2.3 on)
+        "VTG" => Sentence::Vtg {
+            course: number(field(0)),
+            speed_knots: number(field(4)),
+            speed_kmh: number(field(6)),
+            valid: field(8) != Some("N"),
+        },
+        // time, lat, N/S, lon, E/W, quality, satellites, ...
+        "GGA" => Sentence::Gga { quality: number(field(5)).unwrap_or(0), satellites: number(field(6)) },
+        _ => return Ok(None),
+    };
+    Ok(Some(sentence))
+}
@@ -0,0 +1,92 @@
+// OBD-II requests and the replies an ELM327 adapter gives for them. A request
+// is mode 01 and a PID in hex ("010D"), the reply comes back as hex bytes
+// ("41 0D 32", mode plus 0x40, the PID, then the data) followed by the `>` prompt.
+
+use anyhow::{anyhow, Result};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pid {
+    // km/h, one byte
+    Speed,
+    // rpm, two bytes in quarter revolutions
+    Rpm,
+    // degrees C, one byte offset by 40
+    Coolant,
+}
+
+impl Pid {
+    pub fn code(self) -> u8 {
+        match self {
+            Pid::Speed => 0x0D,
+            Pid::Rpm => 0x0C,
+            Pid::Coolant => 0x05,
+        }
+    }
+
+    // What to send the adapter, without the trailing \r
+    pub fn request(self) -> String {
+        format!("01{:02X}", self.code())
+    }
+
+    fn len(self) -> usize {
+        match self {
+            Pid::Speed | Pid::Coolant => 1,
+            Pid::Rpm => 2,
+        }
+    }
+
+    pub fn value(self, data: &[u8]) -> f32 {
+        match self {
+            Pid::Speed => data[0] as f32,
+            Pid::Rpm => (data[0] as f32 * 256.0 + data[1] as f32) / 4.0,
+            Pid::Coolant => data[0] as f32 - 40.0,
+        }
+    }
+}
+
+// The value in a reply to `pid`'s request, everything the adapter sent up to
+// the prompt. Ok(None) when the car did not answer (NO DATA), which is what
+// happens with the ignition off.
+pub fn parse_reply(pid: Pid, reply: &str) -> Result<Option<f32>> {
+    let expected = [0x41, pid.code()];
+    let mut no_data = false;
+
+    for line in reply.split(['\r', '\n']).map(str::trim) {
+        match line {
+            // still working out which protocol the car speaks, the answer follows
+            "" | "SEARCHING..." | "BUS INIT: ...OK" => continue,
+            "NO DATA" => no_data = true,
+            "?" => return Err(anyhow!("adapter did not understand {}", pid.request())),
+            _ if line.contains("ERROR") || line.starts_with("UNABLE") || line == "STOPPED" => {
+                return Err(anyhow!("adapter says {}", line));
+            },
+            // the echo of the request, if echo is still on
+            _ if line.replace(' ', "") == pid.request() => continue,
+            _ => {
+                let Some(bytes) = hex_bytes(line) else {
+                    return Err(anyhow!("unexpected reply \"{}\"", line));
+                };
+                // with more than one ECU on the bus, the first one that answers wins
+                if bytes.len() >= 2 + pid.len() && bytes[..2] == expected {
+                    return Ok(Some(pid.value(&bytes[2..])));
+                }
+            },
+        }
+    }
+    if no_data {
+        return Ok(None);
+    }
+    Err(anyhow!("no reply to {} in \"{}\"", pid.request(), reply.trim()))
+}
+
+// "41 0D 32" or, with spaces turned off (ATS0), "410D32"
+fn hex_bytes(line: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = line.bytes().filter(|b| *b != b' ').collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,137 @@
+use anyhow::{anyhow, Result};
+use display_interface::{DataFormat, WriteOnlyDataCommand};
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*, primitives::Rectangle};
+
+use crate::{framebuffer::Framebuffer, hal::{DisplaySink, Rotation}};
+
+// MIPI DCS commands the GC9A01 uses for addressing its frame memory
+const COLUMN_ADDRESS_SET: u8 = 0x2A;
+const ROW_ADDRESS_SET: u8 = 0x2B;
+const MEMORY_WRITE: u8 = 0x2C;
+const MEMORY_ACCESS_CONTROL: u8 = 0x36;
+
+// MADCTL bits: row/column order, row/column exchange, and the BGR order and
+// vertical refresh the gc9a01 driver sets up at init
+const MADCTL_MY: u8 = 0x80;
+const MADCTL_MX: u8 = 0x40;
+const MADCTL_MV: u8 = 0x20;
+const MADCTL_INIT: u8 = 0x18;
+
+// A GC9A01 behind any display-interface bus. Drawing goes into a local frame
+// buffer; flushing opens an address window on the panel for each changed area
+// and streams just those pixels, so small needle moves cost a few KB instead of
+// the whole 115 KB frame.
+pub struct Panel<DI> {
+    iface: DI,
+    frame: Framebuffer,
+}
+
+impl<DI> Panel<DI>
+where
+    DI: WriteOnlyDataCommand,
+{
+    // `iface` must already be talking to an initialised panel
+    pub fn new(iface: DI) -> Self {
+        Self { iface, frame: Framebuffer::new() }
+    }
+
+    pub fn frame(&self) -> &Framebuffer {
+        &self.frame
+    }
+
+    pub fn bus(&self) -> &DI {
+        &self.iface
+    }
+
+    fn command(&mut self, command: u8, params: &[u8]) -> Result<()> {
+        self.iface
+            .send_commands(DataFormat::U8(&[command]))
+            .map_err(|e| anyhow!("Error sending command {:#04x}: {:?}", command, e))?;
+        if !params.is_empty() {
+            self.iface
+                .send_data(DataFormat::U8(params))
+                .map_err(|e| anyhow!("Error sending command {:#04x}: {:?}", command, e))?;
+        }
+        Ok(())
+    }
+
+    // Turns the picture on the panel by changing how it scans its memory, so
+    // drawing and flushing work the same at any rotation
+    pub fn set_rotation(&mut self, rotation: Rotation) -> Result<()> {
+        let madctl = match rotation {
+            Rotation::Deg0 => MADCTL_INIT,
+            Rotation::Deg90 => MADCTL_INIT | MADCTL_MX | MADCTL_MV,
+            Rotation::Deg180 => MADCTL_INIT | MADCTL_MX | MADCTL_MY,
+            Rotation::Deg270 => MADCTL_INIT | MADCTL_MY | MADCTL_MV,
+        };
+        self.command(MEMORY_ACCESS_CONTROL, &[madctl])
+    }
+
+    // Sends the pixels inside `area` (clipped to the screen) to the same place on the panel
+    pub fn write_window(&mut self, area: &Rectangle) -> Result<()> {
+        let area = area.intersection(&self.frame.bounding_box());
+        let Some(bottom_right) = area.bottom_right() else {
+            return Ok(());
+        };
+        let (x0, y0) = (area.top_left.x as u16, area.top_left.y as u16);
+        let (x1, y1) = (bottom_right.x as u16, bottom_right.y as u16);
+
+        self.command(COLUMN_ADDRESS_SET, &[(x0 >> 8) as u8, x0 as u8, (x1 >> 8) as u8, x1 as u8])?;
+        self.command(ROW_ADDRESS_SET, &[(y0 >> 8) as u8, y0 as u8, (y1 >> 8) as u8, y1 as u8])?;
+        self.command(MEMORY_WRITE, &[])?;
+
+        // one row per transfer keeps each write well under the spidev buffer size
+        let width = Framebuffer::WIDTH as usize;
+        let mut row = Vec::with_capacity(area.size.width as usize * 2);
+        for y in y0 as usize..=y1 as usize {
+            row.clear();
+            for pixel in &self.frame.pixels()[y * width + x0 as usize..=y * width + x1 as usize] {
+                row.extend_from_slice(&pixel.into_storage().to_be_bytes());
+            }
+            self.iface
+                .send_data(DataFormat::U8(&row))
+                .map_err(|e| anyhow!("Error sending pixel data: {:?}", e))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<DI> OriginDimensions for Panel<DI> {
+    fn size(&self) -> Size {
+        self.frame.size()
+    }
+}
+
+impl<DI> DrawTarget for Panel<DI> {
+    type Color = Rgb565;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.frame.draw_iter(pixels)
+    }
+}
+
+impl<DI> DisplaySink for Panel<DI>
+where
+    DI: WriteOnlyDataCommand,
+{
+    fn clear_buffer(&mut self) {
+        self.frame.clear();
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let screen = self.frame.bounding_box();
+        self.write_window(&screen)
+    }
+
+    fn flush_regions(&mut self, regions: &[Rectangle]) -> Result<()> {
+        for region in regions {
+            self.write_window(region)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+use gc9a01::{
+    display::DisplayResolution240x240,
+    mode::{BufferedGraphics, DisplayConfiguration},
+    prelude::SPIInterface,
+    rotation::DisplayRotation,
+    Gc9a01, SPIDisplayInterface,
+};
+use rppal::{
+    gpio::{Gpio, InputPin, OutputPin, Trigger},
+    hal::Delay,
+    pwm::{self, Pwm},
+    spi::*,
+};
+
+use crate::{
+    config::DisplayConfig,
+    hal::{Backlight, PulseInput},
+    panel::Panel,
+    source::PulseConfig,
+};
+
+type Interface = SPIInterface<Spi, OutputPin, OutputPin>;
+
+// The gc9a01 driver owns its interface for good, so it gets a shared handle to
+// run the init sequence while the `Panel` keeps another for the window writes
+#[derive(Clone)]
+pub struct SharedInterface(Rc<RefCell<Interface>>);
+
+impl WriteOnlyDataCommand for SharedInterface {
+    fn send_commands(&mut self, cmd: DataFormat<'_>) -> Result<(), DisplayError> {
+        self.0.borrow_mut().send_commands(cmd)
+    }
+
+    fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        self.0.borrow_mut().send_data(buf)
+    }
+}
+
+// GC9A01 panel wired to the Pi's SPI0
+pub struct RpiDisplay {
+    pub panel: Panel<SharedInterface>,
+    // dropping the pin resets it to an input, which lets RST float and resets the panel
+    _reset: OutputPin,
+}
+
+// Backlight driven from the hardware PWM channel
+pub struct PwmBacklight {
+    pwm: Pwm,
+}
+
+// Sets up SPI, GPIO and PWM and initialises the panel. The defaults in
+// `DisplayConfig` match the wiring in the tables below.
+pub fn init(config: &DisplayConfig) -> Result<(RpiDisplay, PwmBacklight)> {
+    // setup of the SPI
+    // Table of GC9A01 driver (https://www.waveshare.com/wiki/1.28inch_LCD_Module) to physical pinout to function to BCM pin (https://pinout.xyz/)
+    // GC9A01 | Pi | SPI      | BCM
+    //  DIN   | 19 | MOSI     | 10
+    //  CLK   | 23 | SCLK     | 11
+    let spi = Spi::new(bus(config.spi_bus)?, slave_select(config.spi_chip_select)?, config.spi_speed_hz, Mode::Mode0)
+        .context("Error setting SPI preferences")?;
+
+    //setup the rest of the pins for Gc9a01 driver
+    // Note: Slave Select(SS) is also know as Chip Enable(CE) or Chip Select(CS)
+    // GC9A01 | Pi | BCM
+    //   CS   | 24 | 8 (CE0)
+    //   DC   | 22 | 25
+    //   RST  | 13 | 27
+    //   BL   | 12 | 18
+    let gpio = Gpio::new().context("Could not set up GPIO")?;
+
+    // CS pin
+    let cs = gpio.get(config.cs_pin).with_context(|| format!("Unable to get pin {} (CS)", config.cs_pin))?.into_output();
+    // Data or Command? pin (Set which mode to be in 0 for command, 1 for data)
+    let dc = gpio.get(config.dc_pin).with_context(|| format!("Unable to get pin {} (DC)", config.dc_pin))?.into_output();
+    // reset pin
+    let mut reset = gpio.get(config.reset_pin).with_context(|| format!("Unable to get pin {} (RST)", config.reset_pin))?.into_output();
+    // backlight pin
+    // The LEDPWM
+    // duty is calculated as DBV[7:0]/255 x period (affected by OSC frequency).
+    // For example: LEDPWM period = 3ms, and DBV[7:0] = ‘200DEC’. Then LEDPWM duty = 200 / 255=78.1%.
+    // Correspond to the LEDPWM period = 3 ms, the high-level of LEDPWM (high effective) = 2.344ms, and the
+    // low-level of LEDPWM = 0.656ms.
+    let period = Duration::from_micros(3_000);
+    let pulse_width = Duration::from_micros(3_000);
+
+    let pwm = Pwm::with_period(
+        match config.pwm_channel {
+            0 => pwm::Channel::Pwm0,
+            1 => pwm::Channel::Pwm1,
+            n => return Err(anyhow!("No PWM channel {}", n)),
+        },
+        period,
+        pulse_width,
+        pwm::Polarity::Normal,
+        true,
+    )
+    .context("Unable to set up PWM")?;
+
+    // create the interface for the display
+    let interface = SharedInterface(Rc::new(RefCell::new(SPIDisplayInterface::new(spi, dc, cs))));
+
+    let mut driver: Gc9a01<SharedInterface, DisplayResolution240x240, BufferedGraphics<DisplayResolution240x240>> =
+        Gc9a01::new(
+            interface.clone(),
+            DisplayResolution240x240,
+            DisplayRotation::Rotate0,
+        )
+        .into_buffered_graphics();
+
+    let mut delay = Delay::new();
+
+    driver.reset(&mut reset, &mut delay).ok();
+    driver.init(&mut delay).ok();
+
+    // from here on all drawing goes through the panel
+    drop(driver);
+
+    let mut panel = Panel::new(interface);
+    panel.set_rotation(config.rotation)?;
+
+    Ok((RpiDisplay { panel, _reset: reset }, PwmBacklight { pwm }))
+}
+
+fn bus(n: u8) -> Result<Bus> {
+    Ok(match n {
+        0 => Bus::Spi0,
+        1 => Bus::Spi1,
+        2 => Bus::Spi2,
+        3 => Bus::Spi3,
+        4 => Bus::Spi4,
+        5 => Bus::Spi5,
+        6 => Bus::Spi6,
+        _ => return Err(anyhow!("No SPI bus {}", n)),
+    })
+}
+
+fn slave_select(n: u8) -> Result<SlaveSelect> {
+    Ok(match n {
+        0 => SlaveSelect::Ss0,
+        1 => SlaveSelect::Ss1,
+        2 => SlaveSelect::Ss2,
+        3 => SlaveSelect::Ss3,
+        4 => SlaveSelect::Ss4,
+        5 => SlaveSelect::Ss5,
+        6 => SlaveSelect::Ss6,
+        7 => SlaveSelect::Ss7,
+        8 => SlaveSelect::Ss8,
+        9 => SlaveSelect::Ss9,
+        10 => SlaveSelect::Ss10,
+        11 => SlaveSelect::Ss11,
+        12 => SlaveSelect::Ss12,
+        13 => SlaveSelect::Ss13,
+        14 => SlaveSelect::Ss14,
+        15 => SlaveSelect::Ss15,
+        _ => return Err(anyhow!("No SPI slave select {}", n)),
+    })
+}
+
+impl Backlight for PwmBacklight {
+    fn set_brightness(&mut self, brightness: u8) -> Result<()> {
+        let pulse_width = match brightness {
+            0 => Duration::from_micros(0),
+            _ => Duration::from_micros((brightness as u64 * 3_000) / 255),
+        };
+        match self.pwm.set_pulse_width(pulse_width) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(anyhow!("Error setting pulse width: {}", e)),
+        }
+    }
+}
+
+// A wheel sensor on a GPIO, one interrupt per pulse
+pub struct GpioPulses {
+    pin: InputPin,
+}
+
+impl GpioPulses {
+    pub fn open(config: &PulseConfig) -> Result<Self> {
+        let gpio = Gpio::new().context("Could not set up GPIO")?;
+        let pin = gpio.get(config.pin).with_context(|| format!("Unable to get pin {} (pulses)", config.pin))?;
+        let (mut pin, trigger) = match config.pull_up {
+            true => (pin.into_input_pullup(), Trigger::FallingEdge),
+            false => (pin.into_input_pulldown(), Trigger::RisingEdge),
+        };
+        pin.set_interrupt(trigger).context("Unable to set up the pulse interrupt")?;
+        Ok(Self { pin })
+    }
+}
+
+impl PulseInput for GpioPulses {
+    fn wait_for_pulse(&mut self, timeout: Duration) -> Result<Option<Instant>> {
+        // interrupts that came in while nobody was waiting still count
+        match self.pin.poll_interrupt(false, Some(timeout)) {
+            Ok(level) => Ok(level.map(|_| Instant::now())),
+            Err(e) => Err(anyhow!("Error waiting for a pulse on GPIO {}: {}", self.pin.pin(), e)),
+        }
+    }
+}
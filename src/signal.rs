@@ -0,0 +1,188 @@
+// Signals packed into CAN frames, as a DBC file describes them: which frame,
+// where the bits are, and how to scale them. Defined in the config or read from
+// the BO_ and SG_ lines of a DBC file.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ByteOrder {
+    // Intel, DBC @1. `start_bit` is the least significant bit.
+    #[default]
+    LittleEndian,
+    // Motorola, DBC @0. `start_bit` is the most significant bit, numbered the DBC
+    // way: bit 7 of byte 0 is 7, bit 0 of byte 1 is 8.
+    BigEndian,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Signal {
+    pub name: String,
+    // CAN ID of the frame that carries it
+    pub id: u32,
+    // 29 bit ID
+    #[serde(default)]
+    pub extended: bool,
+    pub start_bit: u16,
+    pub length: u16,
+    #[serde(default)]
+    pub byte_order: ByteOrder,
+    #[serde(default)]
+    pub signed: bool,
+    // value = raw * scale + offset
+    #[serde(default = "one")]
+    pub scale: f64,
+    #[serde(default)]
+    pub offset: f64,
+    #[serde(default)]
+    pub unit: String,
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+impl Signal {
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=64).contains(&self.length) {
+            return Err(anyhow!("{}: length has to be 1 to 64 bits, got {}", self.name, self.length));
+        }
+        let max_id = if self.extended { 0x1FFF_FFFF } else { 0x7FF };
+        if self.id > max_id {
+            return Err(anyhow!("{}: ID {:#X} does not fit in {} bits", self.name, self.id, if self.extended { 29 } else { 11 }));
+        }
+        // checked before `end`, which would overflow on a start bit far out of range
+        if self.start_bit > 63 {
+            return Err(anyhow!("{}: start bit has to be 0 to 63, got {}", self.name, self.start_bit));
+        }
+        if self.end() > 64 {
+            return Err(anyhow!("{}: bits {} to {} do not fit in 8 bytes", self.name, self.start_bit, self.end()));
+        }
+        if !self.scale.is_finite() || !self.offset.is_finite() {
+            return Err(anyhow!("{}: scale and offset have to be numbers", self.name));
+        }
+        Ok(())
+    }
+
+    // Where the signal starts in the frame read as one big endian number, bit 0
+    // being the top bit of byte 0
+    fn msb(&self) -> u16 {
+        (self.start_bit / 8) * 8 + (7 - self.start_bit % 8)
+    }
+
+    // One past the last bit the signal covers, counted so the frame has to be at
+    // least this many bits long
+    fn end(&self) -> u16 {
+        match self.byte_order {
+            ByteOrder::LittleEndian => self.start_bit + self.length,
+            ByteOrder::BigEndian => self.msb() + self.length,
+        }
+    }
+
+    // The scaled value out of a frame's data, None when the frame is too short
+    pub fn decode(&self, data: &[u8]) -> Option<f64> {
+        if data.len() > 8 || (self.end() as usize) > data.len() * 8 {
+            return None;
+        }
+        let mut bytes = [0; 8];
+        bytes[..data.len()].copy_from_slice(data);
+
+        let mask = if self.length == 64 { u64::MAX } else { (1 << self.length) - 1 };
+        let raw = match self.byte_order {
+            ByteOrder::LittleEndian => (u64::from_le_bytes(bytes) >> self.start_bit) & mask,
+            ByteOrder::BigEndian => (u64::from_be_bytes(bytes) >> (64 - self.end())) & mask,
+        };
+        let raw = match self.signed {
+            // sign extend from `length` bits
+            true => ((raw << (64 - self.length)) as i64 >> (64 - self.length)) as f64,
+            false => raw as f64,
+        };
+        Some(raw * self.scale + self.offset)
+    }
+}
+
+// The signals in a DBC file. Multiplexed signals are left out, they only mean
+// something next to their multiplexor's value.
+pub fn parse_dbc(text: &str) -> Result<Vec<Signal>> {
+    let mut signals = Vec::new();
+    // the message the SG_ lines below belong to
+    let mut message = None;
+
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("BO_ ") {
+            message = Some(parse_message(rest).with_context(|| format!("line {}", n + 1))?);
+        } else if let Some(rest) = line.strip_prefix("SG_ ") {
+            let (id, extended) = message.ok_or_else(|| anyhow!("line {}: SG_ outside of a BO_", n + 1))?;
+            if let Some(signal) = parse_signal(rest, id, extended).with_context(|| format!("line {}", n + 1))? {
+                signals.push(signal);
+            }
+        }
+    }
+    Ok(signals)
+}
+
+// `BO_ 416 WheelSpeeds: 8 ABS`, the ID with bit 31 set for an extended one
+fn parse_message(rest: &str) -> Result<(u32, bool)> {
+    let id: u32 = number(rest.split_whitespace().next())?;
+    Ok((id & 0x1FFF_FFFF, id & 0x8000_0000 != 0))
+}
+
+// `SG_ WheelSpeedFL : 7|16@0+ (0.01,0) [0|300] "km/h" Dashboard`
+fn parse_signal(rest: &str, id: u32, extended: bool) -> Result<Option<Signal>> {
+    let (names, spec) = rest.split_once(':').ok_or_else(|| anyhow!("SG_ without a ':'"))?;
+    let mut names = names.split_whitespace();
+    let name = names.next().ok_or_else(|| anyhow!("SG_ without a name"))?;
+    // "M" is the multiplexor itself and fine, "m3" only counts when it is 3
+    if names.next().is_some_and(|m| m.starts_with('m')) {
+        return Ok(None);
+    }
+
+    let spec = spec.trim();
+    let (bits, rest) = spec.split_once(' ').ok_or_else(|| anyhow!("{}: no scale", name))?;
+    let (start_bit, rest_bits) = bits.split_once('|').ok_or_else(|| anyhow!("{}: bad bits \"{}\"", name, bits))?;
+    let (length, kind) = rest_bits.split_once('@').ok_or_else(|| anyhow!("{}: bad bits \"{}\"", name, bits))?;
+    let byte_order = match kind.get(..1) {
+        Some("1") => ByteOrder::LittleEndian,
+        Some("0") => ByteOrder::BigEndian,
+        _ => return Err(anyhow!("{}: bad byte order \"{}\"", name, kind)),
+    };
+    let signed = match kind.get(1..) {
+        Some("+") => false,
+        Some("-") => true,
+        _ => return Err(anyhow!("{}: bad sign \"{}\"", name, kind)),
+    };
+
+    let factors = between(rest, '(', ')').ok_or_else(|| anyhow!("{}: no (scale,offset)", name))?;
+    let (scale, offset) = factors.split_once(',').ok_or_else(|| anyhow!("{}: bad (scale,offset)", name))?;
+    let unit = between(rest, '"', '"').unwrap_or_default();
+
+    let signal = Signal {
+        name: name.to_string(),
+        id,
+        extended,
+        start_bit: number(Some(start_bit))?,
+        length: number(Some(length))?,
+        byte_order,
+        signed,
+        scale: number(Some(scale))?,
+        offset: number(Some(offset))?,
+        unit: unit.to_string(),
+    };
+    signal.validate()?;
+    Ok(Some(signal))
+}
+
+fn number<T: FromStr>(s: Option<&str>) -> Result<T> {
+    let s = s.unwrap_or_default().trim();
+    s.parse().map_err(|_| anyhow!("\"{}\" is not a number", s))
+}
+
+fn between(s: &str, open: char, close: char) -> Option<&str> {
+    let (_, rest) = s.split_once(open)?;
+    Some(rest.split_once(close)?.0)
+}
@@ -0,0 +1,86 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::Result;
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+use log::debug;
+
+use crate::{framebuffer::Framebuffer, hal::{Backlight, DisplaySink, Rotation}};
+
+// In-memory stand-in for the GC9A01 panel so the gauge can be drawn without a Pi.
+// Every flush writes the frame to `<out_dir>/speedometer.ppm`, which most image
+// viewers will reload when it changes.
+pub struct Simulator {
+    frame: Framebuffer,
+    out_dir: PathBuf,
+    rotation: Rotation,
+}
+
+impl Simulator {
+    pub fn new(out_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            frame: Framebuffer::new(),
+            out_dir: out_dir.into(),
+            rotation: Rotation::Deg0,
+        }
+    }
+
+    // Turns the written frames the way the panel would show them
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    pub fn frame(&self) -> &Framebuffer {
+        &self.frame
+    }
+}
+
+impl OriginDimensions for Simulator {
+    fn size(&self) -> Size {
+        self.frame.size()
+    }
+}
+
+impl DrawTarget for Simulator {
+    type Color = Rgb565;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.frame.draw_iter(pixels)
+    }
+}
+
+impl DisplaySink for Simulator {
+    fn clear_buffer(&mut self) {
+        self.frame.clear();
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        fs::create_dir_all(&self.out_dir)?;
+
+        // write to a temp file and rename so viewers never see a half written frame
+        let tmp = self.out_dir.join(".speedometer.ppm");
+        match self.rotation {
+            Rotation::Deg0 => self.frame.write_ppm(&tmp)?,
+            rotation => self.frame.rotated(rotation).write_ppm(&tmp)?,
+        }
+        fs::rename(tmp, self.out_dir.join("speedometer.ppm"))?;
+        Ok(())
+    }
+}
+
+// There is no backlight to dim, so the level is only remembered and logged
+#[derive(Debug, Default)]
+pub struct SimulatedBacklight {
+    pub brightness: u8,
+}
+
+impl Backlight for SimulatedBacklight {
+    fn set_brightness(&mut self, brightness: u8) -> Result<()> {
+        self.brightness = brightness;
+        debug!("Backlight at {}", brightness);
+        Ok(())
+    }
+}
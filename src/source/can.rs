@@ -0,0 +1,159 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    signal::{self, Signal},
+    speed::SpeedUnit,
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CanConfig {
+    // SocketCAN interface, vcan0 for testing
+    pub interface: String,
+    // name of the signal that is the speed
+    pub speed_signal: String,
+    // DBC file whose signals are added to the ones below
+    pub dbc: Option<PathBuf>,
+    pub signals: Vec<Signal>,
+}
+
+impl Default for CanConfig {
+    fn default() -> Self {
+        Self { interface: "can0".to_string(), speed_signal: "speed".to_string(), dbc: None, signals: Vec::new() }
+    }
+}
+
+impl CanConfig {
+    // All the signals, with the DBC file's, after checking the speed is among them
+    // and has a unit the gauge knows
+    pub fn load_signals(&self) -> Result<Vec<Signal>> {
+        let mut signals = self.signals.clone();
+        if let Some(path) = &self.dbc {
+            let text = fs::read_to_string(path).with_context(|| format!("Unable to read {}", path.display()))?;
+            signals.extend(signal::parse_dbc(&text).with_context(|| format!("Invalid DBC {}", path.display()))?);
+        }
+        for signal in &signals {
+            signal.validate()?;
+        }
+
+        let speed = signals
+            .iter()
+            .find(|s| s.name == self.speed_signal)
+            .ok_or_else(|| anyhow!("no signal called \"{}\" for the speed", self.speed_signal))?;
+        speed_unit(speed)?;
+        Ok(signals)
+    }
+}
+
+// The speed signal's unit, none for the gauge's
+fn speed_unit(signal: &Signal) -> Result<Option<SpeedUnit>> {
+    match signal.unit.trim() {
+        "" => Ok(None),
+        unit => Ok(Some(unit.parse().with_context(|| format!("{} is the speed", signal.name))?)),
+    }
+}
+
+#[cfg(feature = "can")]
+pub use socket::CanSource;
+
+#[cfg(feature = "can")]
+mod socket {
+    use std::collections::HashMap;
+
+    use anyhow::{anyhow, Context, Result};
+    use log::{debug, warn};
+    use socketcan::{CanFrame, CanSocket, EmbeddedFrame, Id, Socket};
+
+    use super::{speed_unit, CanConfig};
+    use crate::{
+        hal::SpeedSource,
+        signal::Signal,
+        speed::{Speed, SpeedUnit},
+    };
+
+    // Decodes the configured signals out of the frames on a SocketCAN interface.
+    // The speed signal is the reading, the others are kept as channels.
+    pub struct CanSource {
+        socket: CanSocket,
+        interface: String,
+        speed_signal: String,
+        unit: Option<SpeedUnit>,
+        frames: HashMap<(u32, bool), Vec<Signal>>,
+        channels: HashMap<String, f64>,
+    }
+
+    impl CanSource {
+        pub fn open(config: &CanConfig) -> Result<Self> {
+            let signals = config.load_signals()?;
+            let unit = match signals.iter().find(|s| s.name == config.speed_signal) {
+                Some(signal) => speed_unit(signal)?,
+                None => None,
+            };
+            let socket = CanSocket::open(&config.interface)
+                .with_context(|| format!("Unable to open CAN interface {}", config.interface))?;
+
+            Ok(Self {
+                socket,
+                interface: config.interface.clone(),
+                speed_signal: config.speed_signal.clone(),
+                unit,
+                frames: by_frame(signals),
+                channels: HashMap::new(),
+            })
+        }
+
+        // The last value of every signal seen so far, by name
+        pub fn channels(&self) -> &HashMap<String, f64> {
+            &self.channels
+        }
+    }
+
+    // The frames the signals come in, by ID and whether that ID is extended
+    fn by_frame(signals: Vec<Signal>) -> HashMap<(u32, bool), Vec<Signal>> {
+        let mut frames: HashMap<(u32, bool), Vec<Signal>> = HashMap::new();
+        for signal in signals {
+            frames.entry((signal.id, signal.extended)).or_default().push(signal);
+        }
+        frames
+    }
+
+    impl SpeedSource for CanSource {
+        fn next_speed(&mut self) -> Result<Speed> {
+            loop {
+                let frame = self.socket.read_frame().map_err(|e| anyhow!("Error reading {}: {}", self.interface, e))?;
+                let key = match frame {
+                    CanFrame::Data(_) => match frame.id() {
+                        Id::Standard(id) => (id.as_raw() as u32, false),
+                        Id::Extended(id) => (id.as_raw(), true),
+                    },
+                    CanFrame::Error(e) => {
+                        warn!("Error frame on {}: {:?}", self.interface, e);
+                        continue;
+                    },
+                    CanFrame::Remote(_) => continue,
+                };
+                let Some(signals) = self.frames.get(&key) else {
+                    continue;
+                };
+
+                let mut speed = None;
+                for signal in signals {
+                    let Some(value) = signal.decode(frame.data()) else {
+                        debug!("Frame {:#X} on {} is too short for {}", key.0, self.interface, signal.name);
+                        continue;
+                    };
+                    if signal.name == self.speed_signal {
+                        speed = Some(value);
+                    }
+                    self.channels.insert(signal.name.clone(), value);
+                }
+                if let Some(value) = speed {
+                    return Speed::checked(value as f32, self.unit);
+                }
+            }
+        }
+    }
+}
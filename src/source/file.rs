@@ -0,0 +1,158 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use notify::{
+    event::{AccessKind, AccessMode, ModifyKind, RenameMode},
+    Config, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use serde::Deserialize;
+
+use crate::{hal::SpeedSource, speed::Speed};
+
+// Which writes to the file count as a new reading
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchMode {
+    // any change, as soon as it happens. A writer that is slow about it can be
+    // read half way through.
+    #[default]
+    Modify,
+    // only once the writer closes the file (inotify IN_CLOSE_WRITE), Linux only
+    CloseWrite,
+}
+
+// Reads the speed out of a text file every time it is rewritten. The directory
+// is watched rather than the file, so writers that replace the file (write a
+// temp file and rename it over, or delete and recreate it) keep working.
+pub struct FileSource {
+    path: PathBuf,
+    dir: PathBuf,
+    file_name: OsString,
+    mode: WatchMode,
+    rx: Receiver<notify::Result<notify::Event>>,
+    watcher: RecommendedWatcher,
+    // false once the directory itself went away, until it is watched again
+    armed: bool,
+    // the file has to be read before waiting for more events
+    pending: bool,
+}
+
+impl FileSource {
+    // How often a lost watch is retried
+    const REARM_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_mode(path, WatchMode::Modify)
+    }
+
+    pub fn with_mode(path: impl AsRef<Path>, mode: WatchMode) -> Result<Self> {
+        if mode == WatchMode::CloseWrite && !cfg!(target_os = "linux") {
+            return Err(anyhow!("close_write watching needs inotify, which is Linux only"));
+        }
+
+        let path = path.as_ref().to_path_buf();
+        let file_name = path.file_name().ok_or_else(|| anyhow!("{} is not a file", path.display()))?.to_owned();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        // Create a channel to receive the events.
+        let (tx, rx) = channel();
+
+        let mut watcher = RecommendedWatcher::new(tx, Config::default())?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(|e| anyhow!("Unable to watch {}: {}", dir.display(), e))?;
+
+        // start with whatever is in the file already
+        Ok(Self { path, dir, file_name, mode, rx, watcher, armed: true, pending: true })
+    }
+
+    // None when there is nothing to read yet: the file is missing, or empty
+    // because the writer has only truncated it so far
+    fn read(&self) -> Option<Result<Speed>> {
+        match fs::read_to_string(&self.path) {
+            Ok(s) if s.trim().is_empty() => None,
+            Ok(s) => Some(s.parse::<Speed>()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => Some(Err(anyhow!("Error reading {}: {}", self.path.display(), e))),
+        }
+    }
+
+    fn handle(&mut self, event: notify::Event) {
+        if event.paths.iter().any(|p| p == &self.dir) {
+            if matches!(event.kind, EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))) {
+                warn!("{} is gone, waiting for it to come back", self.dir.display());
+                self.armed = false;
+            }
+            return;
+        }
+        if !event.paths.iter().any(|p| p.file_name() == Some(self.file_name.as_os_str())) {
+            return;
+        }
+
+        match (event.kind, self.mode) {
+            // renamed into place, which is how atomic writers finish. inotify also
+            // sends Both for the same rename, To alone is enough.
+            (EventKind::Modify(ModifyKind::Name(RenameMode::To | RenameMode::Any)), _) => self.pending = true,
+            (EventKind::Modify(ModifyKind::Name(RenameMode::From)) | EventKind::Remove(_), _) => {
+                debug!("{} went away, waiting for it to come back", self.path.display());
+            },
+            (EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any), WatchMode::Modify) => {
+                self.pending = true;
+            },
+            (EventKind::Access(AccessKind::Close(AccessMode::Write)), WatchMode::CloseWrite) => self.pending = true,
+            _ => {},
+        }
+    }
+
+    fn rearm(&mut self) {
+        // the old watch may or may not still be there
+        self.watcher.unwatch(&self.dir).ok();
+        if self.watcher.watch(&self.dir, RecursiveMode::NonRecursive).is_ok() {
+            info!("Watching {} again", self.dir.display());
+            self.armed = true;
+            // it may have been written while nobody was looking
+            self.pending = true;
+        }
+    }
+}
+
+impl SpeedSource for FileSource {
+    fn next_speed(&mut self) -> Result<Speed> {
+        loop {
+            if self.pending {
+                self.pending = false;
+                if let Some(speed) = self.read() {
+                    return speed;
+                }
+            }
+
+            match self.rx.recv_timeout(Self::REARM_INTERVAL) {
+                Ok(Ok(event)) => self.handle(event),
+                Ok(Err(e)) => {
+                    warn!("Watch error on {}: {}", self.dir.display(), e);
+                    self.armed = false;
+                },
+                Err(RecvTimeoutError::Timeout) => {},
+                Err(RecvTimeoutError::Disconnected) => return Err(anyhow!("File watcher stopped")),
+            }
+
+            if self.armed && !self.dir.is_dir() {
+                warn!("{} is gone, waiting for it to come back", self.dir.display());
+                self.armed = false;
+            }
+            if !self.armed {
+                self.rearm();
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use serde::Deserialize;
+
+use crate::{
+    hal::SpeedSource,
+    speed::{Speed, SpeedUnit},
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GpsdConfig {
+    // host:port gpsd listens on
+    pub address: String,
+}
+
+impl Default for GpsdConfig {
+    fn default() -> Self {
+        Self { address: "127.0.0.1:2947".to_string() }
+    }
+}
+
+// The reports we care about, gpsd sends plenty of others (VERSION, DEVICES, SKY ...)
+#[derive(Debug, Deserialize)]
+#[serde(tag = "class")]
+enum Report {
+    #[serde(rename = "TPV")]
+    Tpv(Tpv),
+    #[serde(other)]
+    Other,
+}
+
+// Time-position-velocity, see gpsd_json(5)
+#[derive(Debug, Deserialize)]
+struct Tpv {
+    // 0 unknown, 1 no fix, 2 2D, 3 3D
+    #[serde(default)]
+    mode: u8,
+    // speed over ground, m/s
+    speed: Option<f32>,
+    // course over ground, degrees true
+    track: Option<f32>,
+}
+
+// Speed over ground from a running gpsd, via its JSON protocol. A lost connection
+// is an error once, then it keeps trying to connect again, a second apart until
+// gpsd has sent a report, even when it takes each connection and drops it.
+pub struct GpsdSource {
+    address: String,
+    reader: Option<BufReader<TcpStream>>,
+    // the connection problem has been reported, stay quiet until gpsd sends a report
+    reported: bool,
+    // None until gpsd said either way
+    fix: Option<bool>,
+    track: Option<f32>,
+}
+
+impl GpsdSource {
+    const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+    const WATCH: &'static [u8] = b"?WATCH={\"enable\":true,\"json\":true}\n";
+
+    // Connects on the first reading, gpsd may well start after us
+    pub fn new(config: &GpsdConfig) -> Self {
+        Self { address: config.address.clone(), reader: None, reported: false, fix: None, track: None }
+    }
+
+    // Course over ground from the last report that had one
+    pub fn track(&self) -> Option<f32> {
+        self.track
+    }
+
+    fn connect(&self) -> Result<BufReader<TcpStream>> {
+        let mut stream =
+            TcpStream::connect(&self.address).with_context(|| format!("Unable to connect to gpsd at {}", self.address))?;
+        stream.write_all(Self::WATCH)?;
+        Ok(BufReader::new(stream))
+    }
+
+    fn lost(&mut self, e: anyhow::Error) -> Result<Speed> {
+        self.reader = None;
+        self.fix = None;
+        self.reported = true;
+        Err(e)
+    }
+
+    fn tpv(&mut self, tpv: Tpv) -> Option<Result<Speed>> {
+        let valid = tpv.mode >= 2;
+        match (self.fix.replace(valid), valid) {
+            (Some(false), false) => return None,
+            (_, false) => return Some(Err(anyhow!("no fix"))),
+            (Some(true), true) => {},
+            (_, true) => info!("GPS fix from gpsd at {}, mode {}", self.address, tpv.mode),
+        }
+        self.track = tpv.track.or(self.track);
+        Some(Speed::checked(tpv.speed?, Some(SpeedUnit::MetersPerSecond)))
+    }
+}
+
+impl SpeedSource for GpsdSource {
+    fn next_speed(&mut self) -> Result<Speed> {
+        let mut line = String::new();
+        loop {
+            let Some(reader) = &mut self.reader else {
+                if self.reported {
+                    thread::sleep(Self::RETRY_INTERVAL);
+                }
+                match self.connect() {
+                    Ok(reader) => {
+                        if !self.reported {
+                            info!("Connected to gpsd at {}", self.address);
+                        }
+                        self.reader = Some(reader);
+                    },
+                    Err(e) if !self.reported => return self.lost(e),
+                    Err(_) => {},
+                }
+                continue;
+            };
+
+            line.clear();
+            let gone = match reader.read_line(&mut line) {
+                Ok(0) => anyhow!("gpsd at {} closed the connection", self.address),
+                Ok(_) => {
+                    let report = serde_json::from_str::<Report>(&line);
+                    if report.is_ok() && self.reported {
+                        info!("gpsd at {} is back", self.address);
+                        self.reported = false;
+                    }
+                    match report {
+                        Ok(Report::Tpv(tpv)) => {
+                            if let Some(speed) = self.tpv(tpv) {
+                                return speed;
+                            }
+                        },
+                        Ok(Report::Other) => {},
+                        Err(e) => debug!("Skipped gpsd report: {}: {}", e, line.trim_end()),
+                    }
+                    continue;
+                },
+                Err(e) => anyhow!("Lost gpsd at {}: {}", self.address, e),
+            };
+            // when already said, this was just another try that did not work out
+            let quiet = self.reported;
+            let lost = self.lost(gone);
+            if !quiet {
+                return lost;
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    io::Read,
+    net::SocketAddr,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    thread,
+};
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, warn};
+use serde::Deserialize;
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+use tungstenite::{handshake::derive_accept_key, protocol::Role, Message, WebSocket};
+
+use crate::{hal::SpeedSource, source::lines::parse_line, speed::Speed};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    // address the server listens on
+    pub bind: String,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self { bind: "0.0.0.0:8080".to_string() }
+    }
+}
+
+// A small HTTP server to push the speed to. `POST /speed` takes one sample in the
+// body, `/ws` upgrades to a WebSocket that takes a sample per text message for
+// senders with a lot to say. Samples are text or JSON, see `parse_line`.
+pub struct HttpSource {
+    server: Arc<Server>,
+    rx: Receiver<Result<Speed>>,
+}
+
+impl HttpSource {
+    // nothing sensible is longer, see `parse_line`
+    const MAX_BODY: u64 = 1024;
+
+    pub fn bind(config: &HttpConfig) -> Result<Self> {
+        let server = Server::http(&config.bind).map_err(|e| anyhow!("Unable to listen on {}: {}", config.bind, e))?;
+        let server = Arc::new(server);
+
+        let (tx, rx) = channel();
+        let serving = server.clone();
+        thread::spawn(move || serve(&serving, tx));
+        Ok(Self { server, rx })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.server.server_addr().to_ip().ok_or_else(|| anyhow!("not listening on an IP address"))
+    }
+}
+
+impl Drop for HttpSource {
+    fn drop(&mut self) {
+        self.server.unblock();
+    }
+}
+
+fn serve(server: &Server, tx: Sender<Result<Speed>>) {
+    for request in server.incoming_requests() {
+        let path = request.url().split('?').next().unwrap_or_default().to_string();
+        debug!("{} {} from {:?}", request.method(), path, request.remote_addr());
+        let sample = match (request.method(), path.as_str()) {
+            (Method::Post, "/speed") => post(request),
+            (Method::Get, "/ws") => {
+                upgrade(request, tx.clone());
+                continue;
+            },
+            (_, "/speed" | "/ws") => {
+                respond(request, 405, "method not allowed\n");
+                continue;
+            },
+            _ => {
+                respond(request, 404, "not found\n");
+                continue;
+            },
+        };
+        if tx.send(sample).is_err() {
+            break;
+        }
+    }
+}
+
+// The body is the sample, the reply says whether it was taken
+fn post(mut request: Request) -> Result<Speed> {
+    let mut body = String::new();
+    let read = request.as_reader().take(HttpSource::MAX_BODY + 1).read_to_string(&mut body);
+    let sample = match read {
+        Ok(n) if n as u64 > HttpSource::MAX_BODY => Err(anyhow!("body over {} bytes", HttpSource::MAX_BODY)),
+        Ok(_) => parse_line(&body),
+        Err(e) => Err(anyhow!("unable to read the body: {}", e)),
+    };
+    match &sample {
+        Ok(_) => respond(request, 204, ""),
+        Err(e) => respond(request, 400, &format!("{:#}\n", e)),
+    }
+    sample.context("Bad sample over HTTP")
+}
+
+fn respond(request: Request, status: u16, body: &str) {
+    let response = Response::from_string(body).with_status_code(StatusCode(status));
+    if let Err(e) = request.respond(response) {
+        debug!("Unable to answer an HTTP client: {}", e);
+    }
+}
+
+fn header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request.headers().iter().find(|h| h.field.equiv(name)).map(|h| h.value.as_str())
+}
+
+// Answers the WebSocket handshake and reads the socket on its own thread
+fn upgrade(request: Request, tx: Sender<Result<Speed>>) {
+    let is_websocket = header(&request, "Upgrade").is_some_and(|u| u.eq_ignore_ascii_case("websocket"));
+    let Some(key) = header(&request, "Sec-WebSocket-Key").filter(|_| is_websocket) else {
+        respond(request, 400, "expected a WebSocket handshake\n");
+        return;
+    };
+
+    let accept = derive_accept_key(key.as_bytes());
+    let response = Response::empty(StatusCode(101))
+        .with_header(Header::from_bytes("Upgrade", "websocket").unwrap())
+        .with_header(Header::from_bytes("Connection", "Upgrade").unwrap())
+        .with_header(Header::from_bytes("Sec-WebSocket-Accept", accept).unwrap());
+    let client = request.remote_addr().copied();
+    let stream = request.upgrade("websocket", response);
+
+    thread::spawn(move || {
+        debug!("WebSocket client {:?} connected", client);
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        loop {
+            let sample = match socket.read() {
+                Ok(Message::Text(text)) if text.trim().is_empty() => continue,
+                Ok(Message::Text(text)) => parse_line(&text),
+                Ok(Message::Binary(_)) => Err(anyhow!("binary message, samples are text")),
+                // pings are answered by tungstenite, a close is seen through to the end
+                Ok(_) => continue,
+                Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => break,
+                Err(e) => {
+                    warn!("Error reading WebSocket client {:?}: {}", client, e);
+                    break;
+                },
+            };
+            if tx.send(sample.context("Bad sample over WebSocket")).is_err() {
+                break;
+            }
+        }
+        debug!("WebSocket client {:?} went away", client);
+    });
+}
+
+impl SpeedSource for HttpSource {
+    fn next_speed(&mut self) -> Result<Speed> {
+        self.rx.recv().map_err(|_| anyhow!("HTTP server stopped"))?
+    }
+}
@@ -0,0 +1,54 @@
+use std::io::BufRead;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::{hal::SpeedSource, source::Ended, speed::Speed};
+
+// A JSON line, `{"speed": 42.5, "unit": "km/h"}`. Other keys are ignored so
+// senders can put more in.
+#[derive(Deserialize)]
+struct Sample {
+    speed: f32,
+    #[serde(default)]
+    unit: Option<String>,
+}
+
+// One sample: either what the speed file may hold ("42", "67.6 km/h") or a
+// JSON object with the speed and optionally its unit
+pub fn parse_line(line: &str) -> Result<Speed> {
+    let line = line.trim();
+    if !line.starts_with('{') {
+        return line.parse();
+    }
+    let sample: Sample = serde_json::from_str(line).map_err(|e| anyhow!("bad JSON speed sample: {}", e))?;
+    let unit = sample.unit.map(|u| u.parse()).transpose()?;
+    Speed::checked(sample.speed, unit)
+}
+
+// A speed sample per line from a stream, stdin for `some_sensor | speedometer
+// --stdin`. Blank lines are skipped, the end of the stream is `Ended`.
+pub struct LineSource<R> {
+    reader: R,
+    line: String,
+}
+
+impl<R: BufRead> LineSource<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, line: String::new() }
+    }
+}
+
+impl<R: BufRead> SpeedSource for LineSource<R> {
+    fn next_speed(&mut self) -> Result<Speed> {
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Err(Ended.into());
+            }
+            if !self.line.trim().is_empty() {
+                return parse_line(&self.line);
+            }
+        }
+    }
+}
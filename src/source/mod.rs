@@ -0,0 +1,44 @@
+pub mod can;
+pub mod file;
+pub mod gpsd;
+pub mod http;
+pub mod lines;
+pub mod mqtt;
+pub mod nmea;
+pub mod obd;
+pub mod pulse;
+pub mod serial;
+pub mod sweep;
+pub mod udp;
+pub mod unix;
+
+pub use can::CanConfig;
+#[cfg(feature = "can")]
+pub use can::CanSource;
+pub use file::{FileSource, WatchMode};
+pub use gpsd::{GpsdConfig, GpsdSource};
+pub use http::{HttpConfig, HttpSource};
+pub use lines::LineSource;
+pub use mqtt::{MqttConfig, MqttSource};
+pub use nmea::{NmeaConfig, NmeaSource};
+pub use obd::{ObdConfig, ObdSource};
+pub use pulse::{PulseConfig, PulseSource};
+pub use serial::{SerialConfig, SerialSource};
+pub use sweep::Sweep;
+pub use udp::{UdpConfig, UdpSource};
+pub use unix::{UnixConfig, UnixSource};
+
+use std::fmt;
+
+// What a source returns once there is nothing more to come, the stream it reads
+// from ended. Anything after that is an error too.
+#[derive(Debug)]
+pub struct Ended;
+
+impl fmt::Display for Ended {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "input ended")
+    }
+}
+
+impl std::error::Error for Ended {}
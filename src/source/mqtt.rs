@@ -0,0 +1,191 @@
+use std::{cmp::min, thread, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use rumqttc::{Client, Connection, Event, MqttOptions, Packet, QoS, SubscribeReasonCode};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    hal::SpeedSource,
+    speed::{Speed, SpeedUnit},
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttConfig {
+    // host:port of the broker
+    pub broker: String,
+    pub topic: String,
+    // 0 at most once, 1 at least once, 2 exactly once
+    pub qos: u8,
+    // where the speed is in a JSON payload, dots for nested objects
+    // ("telemetry.speed"). Payloads that are not JSON are the speed itself.
+    pub field: String,
+    // unit of payloads that are only a number, None for the gauge's
+    pub unit: Option<String>,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub keep_alive_s: u64,
+    // wait this long before connecting again, doubling up to reconnect_max_ms
+    pub reconnect_min_ms: u64,
+    pub reconnect_max_ms: u64,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            broker: "127.0.0.1:1883".to_string(),
+            topic: "vehicle/speed".to_string(),
+            qos: 0,
+            field: "speed".to_string(),
+            unit: None,
+            client_id: "speedometer".to_string(),
+            username: None,
+            password: None,
+            keep_alive_s: 30,
+            reconnect_min_ms: 500,
+            reconnect_max_ms: 30000,
+        }
+    }
+}
+
+impl MqttConfig {
+    pub fn validate(&self) -> Result<()> {
+        self.host_port()?;
+        self.qos()?;
+        self.unit()?;
+        if self.topic.is_empty() {
+            return Err(anyhow!("topic is empty"));
+        }
+        if self.field.split('.').any(str::is_empty) {
+            return Err(anyhow!("bad field \"{}\", expected keys separated by dots", self.field));
+        }
+        if self.keep_alive_s == 0 {
+            return Err(anyhow!("keep_alive_s has to be at least 1"));
+        }
+        if self.reconnect_min_ms == 0 || self.reconnect_max_ms < self.reconnect_min_ms {
+            return Err(anyhow!("reconnect_min_ms has to be above 0 and at most reconnect_max_ms"));
+        }
+        Ok(())
+    }
+
+    fn host_port(&self) -> Result<(&str, u16)> {
+        self.broker
+            .rsplit_once(':')
+            .and_then(|(host, port)| Some((host, port.parse().ok()?)))
+            .filter(|(host, _)| !host.is_empty())
+            .ok_or_else(|| anyhow!("bad broker \"{}\", expected host:port", self.broker))
+    }
+
+    fn qos(&self) -> Result<QoS> {
+        rumqttc::qos(self.qos).map_err(|_| anyhow!("qos has to be 0, 1 or 2, got {}", self.qos))
+    }
+
+    fn unit(&self) -> Result<Option<SpeedUnit>> {
+        self.unit.as_deref().map(str::parse).transpose()
+    }
+}
+
+// The speed in a message: a number or "67.6 km/h" like the speed file, or a
+// JSON object with either at `field`. Bare numbers are in `unit`.
+pub fn parse_payload(payload: &[u8], field: &str, unit: Option<SpeedUnit>) -> Result<Speed> {
+    let text = std::str::from_utf8(payload).map_err(|_| anyhow!("payload is not text"))?.trim();
+    let speed = if text.starts_with('{') {
+        let json: Value = serde_json::from_str(text).map_err(|e| anyhow!("bad JSON payload: {}", e))?;
+        let value = field
+            .split('.')
+            .try_fold(&json, |value, key| value.get(key))
+            .ok_or_else(|| anyhow!("no {} in the payload", field))?;
+        match value {
+            Value::Number(n) => Speed::checked(n.as_f64().unwrap_or(f64::NAN) as f32, None)?,
+            Value::String(s) => s.parse()?,
+            _ => return Err(anyhow!("{} is not a speed: {}", field, value)),
+        }
+    } else {
+        text.parse()?
+    };
+    Ok(Speed { unit: speed.unit.or(unit), ..speed })
+}
+
+// Speeds published to a topic on an MQTT broker. Losing the broker is an error
+// once, which stays on the dial until the next reading; reconnecting backs off
+// from reconnect_min_ms to reconnect_max_ms.
+pub struct MqttSource {
+    config: MqttConfig,
+    qos: QoS,
+    unit: Option<SpeedUnit>,
+    client: Client,
+    connection: Connection,
+    // the current disconnect has been reported, stay quiet until connected
+    reported: bool,
+    backoff: Duration,
+}
+
+impl MqttSource {
+    // Connects on the first reading, the broker may well start after us
+    pub fn new(config: &MqttConfig) -> Result<Self> {
+        config.validate()?;
+        let (host, port) = config.host_port()?;
+        let mut options = MqttOptions::new(&config.client_id, host, port);
+        options.set_keep_alive(Duration::from_secs(config.keep_alive_s));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.as_deref().unwrap_or_default());
+        }
+        let (client, connection) = Client::new(options, 10);
+
+        Ok(Self {
+            config: config.clone(),
+            qos: config.qos()?,
+            unit: config.unit()?,
+            client,
+            connection,
+            reported: false,
+            backoff: Duration::from_millis(config.reconnect_min_ms),
+        })
+    }
+
+    fn disconnected(&mut self, e: anyhow::Error) -> Option<anyhow::Error> {
+        if self.reported {
+            thread::sleep(self.backoff);
+            self.backoff = min(self.backoff * 2, Duration::from_millis(self.config.reconnect_max_ms));
+            return None;
+        }
+        self.reported = true;
+        Some(e.context("MQTT disconnected"))
+    }
+}
+
+impl SpeedSource for MqttSource {
+    fn next_speed(&mut self) -> Result<Speed> {
+        loop {
+            let event = self.connection.recv().map_err(|_| anyhow!("MQTT client stopped"))?;
+            match event {
+                // a clean session every time, so subscribe again
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    info!("Connected to MQTT broker {}, subscribing to {}", self.config.broker, self.config.topic);
+                    self.client.subscribe(&self.config.topic, self.qos)?;
+                    self.reported = false;
+                    self.backoff = Duration::from_millis(self.config.reconnect_min_ms);
+                },
+                Ok(Event::Incoming(Packet::SubAck(ack))) => {
+                    if ack.return_codes.contains(&SubscribeReasonCode::Failure) {
+                        return Err(anyhow!("MQTT broker refused the subscription to {}", self.config.topic));
+                    }
+                },
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    return parse_payload(&publish.payload, &self.config.field, self.unit)
+                        .with_context(|| format!("Bad speed on {}", publish.topic));
+                },
+                Ok(event) => debug!("MQTT {:?}", event),
+                Err(e) => {
+                    let e = anyhow!("{} at {}", e, self.config.broker);
+                    if let Some(e) = self.disconnected(e) {
+                        return Err(e);
+                    }
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use serde::Deserialize;
+
+use crate::{
+    hal::SpeedSource,
+    nmea::{self, Sentence},
+    speed::{Speed, SpeedUnit},
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NmeaConfig {
+    // a serial device (/dev/ttyACM0, /dev/serial0), a recorded log, or "-" for stdin
+    pub device: String,
+    // only used for serial devices
+    pub baud: u32,
+}
+
+impl Default for NmeaConfig {
+    fn default() -> Self {
+        Self { device: "/dev/ttyACM0".to_string(), baud: 9600 }
+    }
+}
+
+// What the receiver last said about its fix
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Fix {
+    // None until the receiver said either way
+    pub valid: Option<bool>,
+    // GGA fix quality, 0 is none
+    pub quality: u8,
+    pub satellites: Option<u8>,
+    // course over ground, degrees true
+    pub heading: Option<f32>,
+}
+
+// Speed over ground from a GPS receiver's NMEA sentences (RMC, VTG, GGA). Losing
+// the fix is an error, once, until the fix is back.
+pub struct NmeaSource {
+    reader: Box<dyn BufRead + Send>,
+    name: String,
+    line: Vec<u8>,
+    fix: Fix,
+    // the input hit its end, see next_line
+    ended: bool,
+}
+
+impl NmeaSource {
+    // How often the input is checked again after it ended
+    const EOF_POLL: Duration = Duration::from_millis(200);
+
+    pub fn open(config: &NmeaConfig) -> Result<Self> {
+        let device = config.device.as_str();
+        if device == "-" {
+            return Ok(Self::new(BufReader::new(io::stdin()), "stdin"));
+        }
+
+        let metadata = fs::metadata(device).with_context(|| format!("Unable to open {}", device))?;
+        if is_char_device(&metadata) {
+            // wait as long as it takes for the receiver to say something
+            let port = serialport::new(device, config.baud)
+                .timeout(Duration::from_secs(3600))
+                .open()
+                .with_context(|| format!("Unable to open {}", device))?;
+            Ok(Self::new(BufReader::new(port), device))
+        } else {
+            let file = File::open(device).with_context(|| format!("Unable to open {}", device))?;
+            Ok(Self::new(BufReader::new(file), device))
+        }
+    }
+
+    pub fn new(reader: impl BufRead + Send + 'static, name: &str) -> Self {
+        Self { reader: Box::new(reader), name: name.to_string(), line: Vec::new(), fix: Fix::default(), ended: false }
+    }
+
+    pub fn fix(&self) -> Fix {
+        self.fix
+    }
+
+    // The next whole line. At the end of the input that is an error once, after
+    // that it waits for more, so a log that is still being written is followed.
+    fn next_line(&mut self) -> Result<String> {
+        loop {
+            match self.reader.read_until(b'\n', &mut self.line) {
+                Ok(0) if self.ended => thread::sleep(Self::EOF_POLL),
+                Ok(0) => {
+                    self.ended = true;
+                    return Err(anyhow!("NMEA input from {} ended", self.name));
+                },
+                Ok(_) if self.line.ends_with(b"\n") => {
+                    self.ended = false;
+                    let line = String::from_utf8_lossy(&self.line).into_owned();
+                    self.line.clear();
+                    return Ok(line);
+                },
+                // the rest of the line has not been written yet
+                Ok(_) => {},
+                Err(e) if e.kind() == io::ErrorKind::TimedOut || e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(anyhow!("Error reading {}: {}", self.name, e)),
+            }
+        }
+    }
+
+    // Err when the fix went away with this sentence
+    fn set_valid(&mut self, valid: bool) -> Result<()> {
+        let was = self.fix.valid.replace(valid);
+        match (was, valid) {
+            (Some(true), true) => Ok(()),
+            (_, true) => {
+                info!("GPS fix on {}, {} satellites", self.name, self.fix.satellites.unwrap_or(0));
+                Ok(())
+            },
+            (Some(false), false) => Ok(()),
+            (_, false) => Err(anyhow!("no fix")),
+        }
+    }
+}
+
+impl SpeedSource for NmeaSource {
+    fn next_speed(&mut self) -> Result<Speed> {
+        loop {
+            let line = self.next_line()?;
+            let sentence = match nmea::parse(&line) {
+                Ok(Some(sentence)) => sentence,
+                Ok(None) => continue,
+                // a line cut short when the port was opened, noise on the wire
+                Err(e) => {
+                    debug!("Skipped NMEA line from {}: {:#}", self.name, e);
+                    continue;
+                },
+            };
+
+            match sentence {
+                Sentence::Rmc { valid, speed_knots, course } => {
+                    self.set_valid(valid)?;
+                    if valid {
+                        self.fix.heading = course.or(self.fix.heading);
+                        if let Some(knots) = speed_knots {
+                            return Speed::checked(knots, Some(SpeedUnit::Knots));
+                        }
+                    }
+                },
+                Sentence::Vtg { speed_knots, speed_kmh, course, valid } => {
+                    if !valid || self.fix.valid == Some(false) {
+                        continue;
+                    }
+                    self.fix.heading = course.or(self.fix.heading);
+                    match (speed_knots, speed_kmh) {
+                        (Some(knots), _) => return Speed::checked(knots, Some(SpeedUnit::Knots)),
+                        (None, Some(kmh)) => return Speed::checked(kmh, Some(SpeedUnit::Kmh)),
+                        (None, None) => {},
+                    }
+                },
+                Sentence::Gga { quality, satellites } => {
+                    self.fix.quality = quality;
+                    self.fix.satellites = satellites;
+                    self.set_valid(quality > 0)?;
+                },
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_char_device(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    metadata.file_type().is_char_device()
+}
+
+#[cfg(not(unix))]
+fn is_char_device(_metadata: &fs::Metadata) -> bool {
+    false
+}
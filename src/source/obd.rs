@@ -0,0 +1,270 @@
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use serde::Deserialize;
+
+use crate::{
+    hal::SpeedSource,
+    obd::{self, Pid},
+    speed::{Speed, SpeedUnit},
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ObdConfig {
+    // a serial device (/dev/ttyUSB0, /dev/rfcomm0 for Bluetooth), or host:port of
+    // a WiFi adapter
+    pub device: String,
+    // only used for serial devices
+    pub baud: u32,
+    // time between speed requests
+    pub poll_interval_ms: u64,
+    // also ask for the engine speed and coolant temperature, which only end up
+    // in the log for now
+    pub rpm: bool,
+    pub coolant: bool,
+}
+
+impl Default for ObdConfig {
+    fn default() -> Self {
+        Self { device: "/dev/ttyUSB0".to_string(), baud: 38400, poll_interval_ms: 100, rpm: false, coolant: false }
+    }
+}
+
+// Whatever carries the bytes to the adapter. Reads have to time out every now
+// and then (a serial port's timeout, a socket's read timeout) rather than block.
+pub trait Link: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Link for T {}
+
+// The last value of each PID, None until the car answered
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Readings {
+    pub speed: Option<f32>,
+    pub rpm: Option<f32>,
+    pub coolant: Option<f32>,
+}
+
+// Polls the vehicle speed from an ELM327 compatible OBD-II adapter. A failing
+// adapter or car is an error once, then it keeps trying to set the adapter up
+// again.
+pub struct ObdSource {
+    config: ObdConfig,
+    link: Option<Box<dyn Link>>,
+    // the adapter has been through ATZ, ATE0, ATSP0
+    ready: bool,
+    // the current problem has been reported, stay quiet until it is over
+    reported: bool,
+    // the car answered NO DATA, reported once
+    no_data: bool,
+    readings: Readings,
+    // speed requests since the adapter was set up
+    polls: u32,
+}
+
+impl ObdSource {
+    const READ_TIMEOUT: Duration = Duration::from_millis(100);
+    // the first request after ATSP0 can take a few seconds while the adapter
+    // tries one protocol after the other
+    const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+    const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+    // coolant temperature hardly moves, ask once every this many speed requests
+    const COOLANT_EVERY: u32 = 50;
+
+    // Connects on the first reading, and again after the link broke
+    pub fn new(config: &ObdConfig) -> Self {
+        Self {
+            config: config.clone(),
+            link: None,
+            ready: false,
+            reported: false,
+            no_data: false,
+            readings: Readings::default(),
+            polls: 0,
+        }
+    }
+
+    // Talks to the adapter over `link`, a pty in the tests for one
+    pub fn with_link(link: Box<dyn Link>, config: &ObdConfig) -> Self {
+        Self { link: Some(link), ..Self::new(config) }
+    }
+
+    pub fn readings(&self) -> Readings {
+        self.readings
+    }
+
+    fn connect(&self) -> Result<Box<dyn Link>> {
+        let device = &self.config.device;
+        if device.starts_with('/') {
+            let port = serialport::new(device, self.config.baud)
+                .timeout(Self::READ_TIMEOUT)
+                .open()
+                .with_context(|| format!("Unable to open {}", device))?;
+            Ok(Box::new(port))
+        } else {
+            let stream = TcpStream::connect(device).with_context(|| format!("Unable to connect to {}", device))?;
+            stream.set_read_timeout(Some(Self::READ_TIMEOUT))?;
+            Ok(Box::new(stream))
+        }
+    }
+
+    // Sends `command` and returns the reply up to the prompt
+    fn command(&mut self, command: &str) -> Result<String> {
+        let device = self.config.device.clone();
+        let link = self.link.as_mut().ok_or_else(|| anyhow!("not connected to {}", device))?;
+        let result = exchange(link.as_mut(), command);
+        // a link that broke is opened again, a slow adapter only set up again
+        if let Err(Exchange::Broken(_)) = &result {
+            self.link = None;
+        }
+        result.map_err(|e| match e {
+            Exchange::Broken(e) => anyhow!("Lost the OBD adapter on {}: {}", device, e),
+            Exchange::Timeout => anyhow!("no reply to {} from the OBD adapter on {}", command, device),
+        })
+    }
+
+    fn init(&mut self) -> Result<()> {
+        // a reset answers with the version, "ELM327 v1.5"
+        let version = self.command("ATZ")?;
+        for command in ["ATE0", "ATSP0"] {
+            let reply = self.command(command)?;
+            if !reply.contains("OK") {
+                return Err(anyhow!("OBD adapter did not take {}: {}", command, reply.trim()));
+            }
+        }
+        info!("OBD adapter on {} is ready: {}", self.config.device, version.trim());
+        Ok(())
+    }
+
+    fn query(&mut self, pid: Pid) -> Result<Option<f32>> {
+        let reply = self.command(&pid.request())?;
+        obd::parse_reply(pid, &reply)
+    }
+
+    // The engine values are nice to have, nothing stops over them
+    fn poll_extras(&mut self) {
+        let mut pids = Vec::new();
+        if self.config.rpm {
+            pids.push(Pid::Rpm);
+        }
+        if self.config.coolant && self.polls % Self::COOLANT_EVERY == 1 {
+            pids.push(Pid::Coolant);
+        }
+        for pid in pids {
+            match self.query(pid) {
+                Ok(Some(value)) => {
+                    debug!("{:?} {}", pid, value);
+                    match pid {
+                        Pid::Rpm => self.readings.rpm = Some(value),
+                        Pid::Coolant => self.readings.coolant = Some(value),
+                        Pid::Speed => {},
+                    }
+                },
+                Ok(None) => {},
+                Err(e) => debug!("No {:?} from the car: {:#}", pid, e),
+            }
+        }
+    }
+
+    // The error to report, if this problem has not been reported yet
+    fn fail(&mut self, e: anyhow::Error) -> Option<anyhow::Error> {
+        self.ready = false;
+        if self.reported {
+            thread::sleep(Self::RETRY_INTERVAL);
+            return None;
+        }
+        self.reported = true;
+        Some(e)
+    }
+}
+
+impl SpeedSource for ObdSource {
+    fn next_speed(&mut self) -> Result<Speed> {
+        loop {
+            if self.link.is_none() {
+                match self.connect() {
+                    Ok(link) => self.link = Some(link),
+                    Err(e) => match self.fail(e) {
+                        Some(e) => return Err(e),
+                        None => continue,
+                    },
+                }
+            }
+            if !self.ready {
+                if let Err(e) = self.init() {
+                    match self.fail(e) {
+                        Some(e) => return Err(e),
+                        None => continue,
+                    }
+                }
+                self.ready = true;
+                self.polls = 0;
+            }
+
+            if self.polls > 0 {
+                thread::sleep(Duration::from_millis(self.config.poll_interval_ms));
+            }
+            self.polls += 1;
+            self.poll_extras();
+
+            match self.query(Pid::Speed) {
+                Ok(Some(kmh)) => {
+                    self.reported = false;
+                    self.no_data = false;
+                    self.readings.speed = Some(kmh);
+                    return Speed::checked(kmh, Some(SpeedUnit::Kmh));
+                },
+                // the ignition is off, or the car has no speed to give
+                Ok(None) => {
+                    self.reported = false;
+                    if !self.no_data {
+                        self.no_data = true;
+                        return Err(anyhow!("no data from the car"));
+                    }
+                },
+                Err(e) => {
+                    if let Some(e) = self.fail(e) {
+                        return Err(e);
+                    }
+                },
+            }
+        }
+    }
+}
+
+enum Exchange {
+    Broken(io::Error),
+    Timeout,
+}
+
+// One command and its reply, all bytes up to the `>` prompt
+fn exchange(link: &mut dyn Link, command: &str) -> Result<String, Exchange> {
+    link.write_all(format!("{}\r", command).as_bytes()).map_err(Exchange::Broken)?;
+
+    let deadline = Instant::now() + ObdSource::REPLY_TIMEOUT;
+    let mut reply = Vec::new();
+    let mut buf = [0; 64];
+    loop {
+        match link.read(&mut buf) {
+            Ok(0) => return Err(Exchange::Broken(io::ErrorKind::UnexpectedEof.into())),
+            Ok(n) => {
+                reply.extend_from_slice(&buf[..n]);
+                if let Some(end) = reply.iter().position(|b| *b == b'>') {
+                    reply.truncate(end);
+                    return Ok(String::from_utf8_lossy(&reply).into_owned());
+                }
+            },
+            Err(e) if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted) => {},
+            Err(e) => return Err(Exchange::Broken(e)),
+        }
+        if Instant::now() > deadline {
+            return Err(Exchange::Timeout);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::{
+    hal::{PulseInput, SpeedSource},
+    speed::{Speed, SpeedUnit},
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PulseConfig {
+    // BCM GPIO the sensor is on
+    pub pin: u8,
+    // pull the pin up and count falling edges, for sensors that switch to ground
+    // (reed switches, open collector hall sensors). Otherwise pulled down, rising edges.
+    pub pull_up: bool,
+    // distance covered in one turn of the wheel
+    pub circumference_m: f32,
+    // magnets (or teeth) per turn
+    pub pulses_per_rev: u32,
+    // edges closer than this to the last one are contact bounce
+    pub debounce_ms: u64,
+    // no pulse for this long means standing still
+    pub zero_timeout_ms: u64,
+    // speed from the mean of this many pulse periods
+    pub average: usize,
+}
+
+impl Default for PulseConfig {
+    fn default() -> Self {
+        Self {
+            pin: 17,
+            pull_up: true,
+            circumference_m: 2.0,
+            pulses_per_rev: 1,
+            debounce_ms: 2,
+            zero_timeout_ms: 2000,
+            average: 4,
+        }
+    }
+}
+
+impl PulseConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.pin > 27 {
+            return Err(anyhow!("pin has to be a GPIO from 0 to 27, got {}", self.pin));
+        }
+        if !(self.circumference_m > 0.0 && self.circumference_m.is_finite()) {
+            return Err(anyhow!("circumference_m has to be above 0, got {}", self.circumference_m));
+        }
+        if self.pulses_per_rev == 0 {
+            return Err(anyhow!("pulses_per_rev has to be at least 1"));
+        }
+        if self.average == 0 {
+            return Err(anyhow!("average has to be at least 1"));
+        }
+        if self.zero_timeout_ms <= self.debounce_ms {
+            return Err(anyhow!("zero_timeout_ms has to be longer than debounce_ms"));
+        }
+        Ok(())
+    }
+}
+
+// Works the speed out of the time between pulses from a wheel sensor. Every
+// pulse after the first is a reading; after `zero_timeout_ms` without one the
+// wheel has stopped and the speed is 0.
+pub struct PulseSource<P> {
+    input: P,
+    // metres covered per pulse
+    distance: f32,
+    debounce: Duration,
+    zero_timeout: Duration,
+    average: usize,
+    // the last pulse that was not bounce
+    last: Option<Instant>,
+    periods: VecDeque<Duration>,
+}
+
+impl<P: PulseInput> PulseSource<P> {
+    pub fn new(input: P, config: &PulseConfig) -> Self {
+        Self {
+            input,
+            distance: config.circumference_m / config.pulses_per_rev as f32,
+            debounce: Duration::from_millis(config.debounce_ms),
+            zero_timeout: Duration::from_millis(config.zero_timeout_ms),
+            average: config.average,
+            last: None,
+            periods: VecDeque::new(),
+        }
+    }
+
+    fn speed(&self) -> Speed {
+        let total: Duration = self.periods.iter().sum();
+        let mean = total.as_secs_f32() / self.periods.len() as f32;
+        Speed::new(self.distance / mean, SpeedUnit::MetersPerSecond)
+    }
+}
+
+impl<P: PulseInput> SpeedSource for PulseSource<P> {
+    fn next_speed(&mut self) -> Result<Speed> {
+        loop {
+            let timeout = match self.last {
+                Some(last) => self.zero_timeout.saturating_sub(last.elapsed()),
+                None => self.zero_timeout,
+            };
+
+            match (self.input.wait_for_pulse(timeout)?, self.last) {
+                (Some(at), Some(last)) if at.saturating_duration_since(last) < self.debounce => {},
+                (Some(at), Some(last)) => {
+                    self.periods.push_back(at.saturating_duration_since(last));
+                    if self.periods.len() > self.average {
+                        self.periods.pop_front();
+                    }
+                    self.last = Some(at);
+                    return Ok(self.speed());
+                },
+                // the first pulse after standing still, the next one gives a period
+                (Some(at), None) => self.last = Some(at),
+                (None, Some(_)) => {
+                    self.last = None;
+                    self.periods.clear();
+                    return Ok(Speed::new(0.0, SpeedUnit::MetersPerSecond));
+                },
+                (None, None) => {},
+            }
+        }
+    }
+}
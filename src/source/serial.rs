@@ -0,0 +1,173 @@
+use std::{
+    io, thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info, trace};
+use serde::Deserialize;
+use serialport::SerialPort;
+
+use crate::{
+    hal::SpeedSource,
+    link::{self, Message, FLAG_SPEED_VALID},
+    speed::Speed,
+};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SerialConfig {
+    // /dev/ttyS0, /dev/ttyUSB0, /dev/serial0 ...
+    pub port: String,
+    pub baud: u32,
+    // no frame for this long and the link counts as lost
+    pub link_timeout_ms: u64,
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self { port: "/dev/serial0".to_string(), baud: 115_200, link_timeout_ms: 1000 }
+    }
+}
+
+// Reads `link` frames from a serial port, the ESP32 end of the project. Corrupt
+// bytes are skipped until the frames line up again, and a link that goes quiet is
+// reported once, then again only after it came back. A port that goes away, like
+// an ESP32 on USB that was unplugged, is opened again once it is back.
+pub struct SerialSource {
+    // None while the port is gone
+    port: Option<Box<dyn SerialPort>>,
+    name: String,
+    // to open the port again with, None for ports without a path
+    path: Option<String>,
+    baud: u32,
+    link_timeout: Duration,
+    // bytes read but not yet made into frames
+    buf: Vec<u8>,
+    last_frame: Instant,
+    // the link or port being down was reported, stay quiet until a frame comes
+    lost: bool,
+}
+
+impl SerialSource {
+    // How long a single read waits, the link timeout is checked in between
+    const POLL: Duration = Duration::from_millis(100);
+    const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn open(config: &SerialConfig) -> Result<Self> {
+        let port = serialport::new(&config.port, config.baud)
+            .timeout(Self::POLL)
+            .open()
+            .with_context(|| format!("Unable to open {}", config.port))?;
+        Ok(Self::new(port, Duration::from_millis(config.link_timeout_ms)))
+    }
+
+    // For ports opened some other way, a pty in the tests for one
+    pub fn new(mut port: Box<dyn SerialPort>, link_timeout: Duration) -> Self {
+        port.set_timeout(Self::POLL).ok();
+        let path = port.name();
+        let name = path.clone().unwrap_or_else(|| "serial port".to_string());
+        let baud = port.baud_rate().unwrap_or(115_200);
+        Self {
+            port: Some(port),
+            name,
+            path,
+            baud,
+            link_timeout,
+            buf: Vec::new(),
+            last_frame: Instant::now(),
+            lost: false,
+        }
+    }
+
+    fn reopen(&self) -> Result<Box<dyn SerialPort>> {
+        let path = self.path.as_deref().ok_or_else(|| anyhow!("{} has no path to open it again", self.name))?;
+        serialport::new(path, self.baud).timeout(Self::POLL).open().with_context(|| format!("Unable to open {}", path))
+    }
+
+    // The next frame already in the buffer, skipping anything that is not one
+    fn next_frame(&mut self) -> Option<Message> {
+        let mut used = 0;
+        let mut message = None;
+        while message.is_none() {
+            match link::decode(&self.buf[used..]) {
+                Ok(None) => break,
+                Ok(Some((m, len))) => {
+                    message = Some(m);
+                    used += len;
+                },
+                Err((e, skip)) => {
+                    // bytes in between frames are only worth a mention when they looked like one
+                    if e != link::FrameError::NoStart {
+                        debug!("Skipped a bad frame on {}: {:?}", self.name, e);
+                    }
+                    used += skip;
+                },
+            }
+        }
+        self.buf.drain(..used);
+        message
+    }
+}
+
+impl SpeedSource for SerialSource {
+    fn next_speed(&mut self) -> Result<Speed> {
+        let mut chunk = [0; 256];
+        loop {
+            while let Some(message) = self.next_frame() {
+                self.last_frame = Instant::now();
+                if self.lost {
+                    info!("Serial link on {} is back", self.name);
+                    self.lost = false;
+                }
+
+                match message {
+                    Message::Telemetry { speed, unit, rpm, flags } => {
+                        trace!("speed {} rpm {} flags {:#04x}", speed, rpm, flags);
+                        if flags & FLAG_SPEED_VALID == 0 {
+                            return Err(anyhow!("no speed from {}", self.name));
+                        }
+                        return Speed::checked(speed, unit);
+                    },
+                    Message::Heartbeat => {},
+                }
+            }
+
+            let Some(port) = &mut self.port else {
+                // give it time to come back, it is only worth a word once it has
+                thread::sleep(Self::RETRY_INTERVAL);
+                if let Ok(port) = self.reopen() {
+                    info!("Opened {} again", self.name);
+                    self.port = Some(port);
+                    self.last_frame = Instant::now();
+                }
+                continue;
+            };
+
+            let gone = match port.read(&mut chunk) {
+                Ok(0) => Some(anyhow!("{} closed", self.name)),
+                Ok(n) => {
+                    self.buf.extend_from_slice(&chunk[..n]);
+                    None
+                },
+                Err(e) if e.kind() == io::ErrorKind::TimedOut || e.kind() == io::ErrorKind::Interrupted => None,
+                Err(e) => Some(anyhow!("Error reading {}: {}", self.name, e)),
+            };
+            if let Some(e) = gone {
+                // every read fails from here on, so drop the port and try to open it again
+                self.port = None;
+                self.buf.clear();
+                if !self.lost {
+                    self.lost = true;
+                    return Err(e);
+                }
+                continue;
+            }
+
+            if !self.lost && self.last_frame.elapsed() > self.link_timeout {
+                self.lost = true;
+                return Err(anyhow!("serial link lost, nothing on {} for {} ms", self.name, self.link_timeout.as_millis()));
+            }
+        }
+    }
+}
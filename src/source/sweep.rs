@@ -0,0 +1,34 @@
+use std::{thread, time::Duration};
+
+use anyhow::Result;
+
+use crate::{hal::SpeedSource, speed::Speed};
+
+// Climbs from `min` to `max` a `step` at a time and starts over, one reading
+// every `interval`. The built-in version of update_speed.sh.
+pub struct Sweep {
+    min: f32,
+    max: f32,
+    step: f32,
+    interval: Duration,
+    next: f32,
+}
+
+impl Sweep {
+    pub fn new(min: f32, max: f32, step: f32, interval: Duration) -> Self {
+        Self { min, max, step, interval, next: min }
+    }
+}
+
+impl SpeedSource for Sweep {
+    fn next_speed(&mut self) -> Result<Speed> {
+        thread::sleep(self.interval);
+
+        let speed = self.next;
+        self.next += self.step;
+        if self.next > self.max {
+            self.next = self.min;
+        }
+        Ok(Speed::bare(speed))
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use serde::Deserialize;
+
+use crate::{hal::SpeedSource, link, speed::Speed};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UdpConfig {
+    // address and port to listen on
+    pub bind: String,
+    // drop binary packets whose timestamp is older than this, 0 to keep them
+    // all. Only useful when the sender's clock is synced (NTP/SNTP).
+    pub max_age_ms: u64,
+}
+
+impl Default for UdpConfig {
+    fn default() -> Self {
+        Self { bind: "0.0.0.0:5005".to_string(), max_age_ms: 0 }
+    }
+}
+
+// The binary datagram, 20 bytes, all big endian:
+//
+//   0  2  magic "SP"
+//   2  1  version, 1
+//   3  1  unit: 0 the gauge's, 1 mph, 2 km/h, 3 m/s, 4 knots
+//   4  4  sequence number, u32, one up per packet, wraps
+//   8  8  timestamp, u64 milliseconds since the Unix epoch, 0 if unknown
+//   16 4  speed, f32
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Packet {
+    pub seq: u32,
+    pub timestamp_ms: u64,
+    pub speed: Speed,
+}
+
+impl Packet {
+    pub const LEN: usize = 20;
+    const MAGIC: [u8; 2] = *b"SP";
+    const VERSION: u8 = 1;
+
+    pub fn encode(&self) -> [u8; Self::LEN] {
+        let mut buf = [0; Self::LEN];
+        buf[0..2].copy_from_slice(&Self::MAGIC);
+        buf[2] = Self::VERSION;
+        buf[3] = link::unit_code(self.speed.unit);
+        buf[4..8].copy_from_slice(&self.seq.to_be_bytes());
+        buf[8..16].copy_from_slice(&self.timestamp_ms.to_be_bytes());
+        buf[16..20].copy_from_slice(&self.speed.value.to_be_bytes());
+        buf
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        if buf.len() != Self::LEN {
+            return Err(anyhow!("speed packet is {} bytes, expected {}", buf.len(), Self::LEN));
+        }
+        if buf[2] != Self::VERSION {
+            return Err(anyhow!("speed packet version {}, expected {}", buf[2], Self::VERSION));
+        }
+        let unit = link::unit_from_code(buf[3]).ok_or_else(|| anyhow!("unknown unit {} in speed packet", buf[3]))?;
+        let seq = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
+        let timestamp_ms = u64::from_be_bytes(buf[8..16].try_into().unwrap());
+        let value = f32::from_be_bytes([buf[16], buf[17], buf[18], buf[19]]);
+
+        Ok(Self { seq, timestamp_ms, speed: Speed::checked(value, unit)? })
+    }
+}
+
+// What a datagram turned out to hold
+enum Datagram {
+    Binary(Packet),
+    Text(Speed),
+}
+
+fn decode(buf: &[u8]) -> Result<Datagram> {
+    if buf.starts_with(&Packet::MAGIC) {
+        return Ok(Datagram::Binary(Packet::decode(buf)?));
+    }
+    let text = std::str::from_utf8(buf).map_err(|_| anyhow!("speed datagram is neither text nor a speed packet"))?;
+    Ok(Datagram::Text(text.parse()?))
+}
+
+// Listens for speed datagrams, either text (anything the speed file may hold,
+// so `echo 42 | nc -u -w0 <pi> 5005` works) or binary `Packet`s. Binary
+// packets that arrive out of order or too late are dropped.
+pub struct UdpSource {
+    socket: UdpSocket,
+    max_age: Option<Duration>,
+    // sequence number and timestamp of the newest binary packet so far
+    last: Option<(u32, u64)>,
+}
+
+impl UdpSource {
+    // packets further back than this are not late, the sender started over
+    const REORDER_WINDOW: i32 = 64;
+
+    pub fn bind(config: &UdpConfig) -> Result<Self> {
+        let socket = UdpSocket::bind(&config.bind).with_context(|| format!("Unable to listen on {}", config.bind))?;
+        let max_age = match config.max_age_ms {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        };
+        Ok(Self { socket, max_age, last: None })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    // Why `packet` should be dropped, if it should
+    fn stale(&self, packet: &Packet) -> Option<String> {
+        if let Some((seq, timestamp_ms)) = self.last {
+            // how far behind the newest packet this one is, wrapping so the
+            // sequence can roll over
+            let behind = seq.wrapping_sub(packet.seq) as i32;
+            // an older sequence with a newer timestamp, or one far further back than
+            // the network would reorder, is a sender that restarted
+            let restarted = packet.timestamp_ms > timestamp_ms || behind > Self::REORDER_WINDOW;
+            if behind >= 0 && !restarted {
+                return Some(format!("out of order, sequence {} after {}", packet.seq, seq));
+            }
+        }
+        if let (Some(max_age), true) = (self.max_age, packet.timestamp_ms > 0) {
+            let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+            let age = now_ms.saturating_sub(packet.timestamp_ms);
+            if age > max_age.as_millis() as u64 {
+                return Some(format!("{} ms old", age));
+            }
+        }
+        None
+    }
+}
+
+impl SpeedSource for UdpSource {
+    fn next_speed(&mut self) -> Result<Speed> {
+        let mut buf = [0; 64];
+        loop {
+            let (len, from) = self.socket.recv_from(&mut buf)?;
+
+            match decode(&buf[..len]).with_context(|| format!("Bad datagram from {}", from))? {
+                Datagram::Text(speed) => return Ok(speed),
+                Datagram::Binary(packet) => {
+                    if let Some(reason) = self.stale(&packet) {
+                        debug!("Dropped speed packet from {}: {}", from, reason);
+                        continue;
+                    }
+                    self.last = Some((packet.seq, packet.timestamp_ms));
+                    return Ok(packet.speed);
+                },
+            }
+        }
+    }
+}
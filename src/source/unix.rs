@@ -0,0 +1,99 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader},
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+};
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, warn};
+use serde::Deserialize;
+
+use crate::{hal::SpeedSource, source::lines::parse_line, speed::Speed};
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UnixConfig {
+    // where the socket is created, a socket left over from a previous run is replaced
+    pub path: PathBuf,
+}
+
+impl Default for UnixConfig {
+    fn default() -> Self {
+        Self { path: PathBuf::from("/tmp/speedometer.sock") }
+    }
+}
+
+// A Unix stream socket other processes on the Pi connect to and write samples
+// to, one per line, text or JSON (see `parse_line`). Any number of clients can be
+// connected at once, `echo 42 | nc -U /tmp/speedometer.sock` is one.
+pub struct UnixSource {
+    path: PathBuf,
+    rx: Receiver<Result<Speed>>,
+}
+
+impl UnixSource {
+    pub fn bind(config: &UnixConfig) -> Result<Self> {
+        let path = config.path.clone();
+        // left behind by a run that did not get to clean up
+        if fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_socket()) {
+            fs::remove_file(&path).with_context(|| format!("Unable to remove the old {}", path.display()))?;
+        }
+        let listener = UnixListener::bind(&path).with_context(|| format!("Unable to listen on {}", path.display()))?;
+
+        let (tx, rx) = channel();
+        thread::spawn(move || accept(listener, tx));
+        Ok(Self { path, rx })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for UnixSource {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+fn accept(listener: UnixListener, tx: Sender<Result<Speed>>) {
+    for (n, stream) in listener.incoming().enumerate() {
+        match stream {
+            Ok(stream) => {
+                let tx = tx.clone();
+                thread::spawn(move || read_client(n + 1, stream, tx));
+            },
+            Err(e) => warn!("Unable to accept a speed client: {}", e),
+        }
+    }
+}
+
+fn read_client(client: usize, stream: UnixStream, tx: Sender<Result<Speed>>) {
+    debug!("Speed client {} connected", client);
+    for line in BufReader::new(stream).lines() {
+        let sample = match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => parse_line(&line).with_context(|| format!("Bad sample from client {}", client)),
+            Err(e) => {
+                warn!("Error reading speed client {}: {}", client, e);
+                break;
+            },
+        };
+        if tx.send(sample).is_err() {
+            break;
+        }
+    }
+    debug!("Speed client {} went away", client);
+}
+
+impl SpeedSource for UnixSource {
+    fn next_speed(&mut self) -> Result<Speed> {
+        self.rx.recv().map_err(|_| anyhow!("Stopped listening on {}", self.path.display()))?
+    }
+}
@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use embedded_graphics::prelude::Point;
+use serde::{Deserialize, Deserializer};
+
+// The scale and geometry of the gauge, the one place the dial, the labels and the
+// needle all take their positions from.
+//
+// Angles are in degrees, clockwise from 3 o'clock (the screen's y axis points
+// down), so the default 180 to 360 sweeps over the top from 9 o'clock round to
+// 3 o'clock. A 270 degree gauge opening at the bottom is 135 to 405.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GaugeSpec {
+    // value at the start and end of the sweep
+    pub min: f32,
+    pub max: f32,
+    pub start_angle: f32,
+    pub end_angle: f32,
+    // ticks are drawn every `minor_tick`, the ones on a multiple of `major_tick` are thicker
+    pub major_tick: f32,
+    pub minor_tick: f32,
+    // ticks on a multiple of `label_step` get a number
+    pub label_step: f32,
+    pub radius: u32,
+    // written as [x, y] in the config
+    #[serde(deserialize_with = "point")]
+    pub center: Point,
+    pub needle_length: u32,
+    pub unit: String,
+    // a pre-rendered 240x240 raw RGB565 dial (like assets/speedometer.raw) shown in
+    // place of the drawn ring, ticks and labels, it has to match the scale above
+    pub background: Option<PathBuf>,
+}
+
+// A tick mark on the dial
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tick {
+    pub value: f32,
+    // radians, ready for cos/sin
+    pub angle: f32,
+    pub major: bool,
+    pub label: bool,
+}
+
+impl GaugeSpec {
+    // Checks the spec describes a gauge that can actually be drawn
+    pub fn validate(&self) -> Result<()> {
+        if !(self.min.is_finite() && self.max.is_finite() && self.max > self.min) {
+            return Err(anyhow!("max ({}) has to be above min ({})", self.max, self.min));
+        }
+        if !(self.start_angle.is_finite() && self.end_angle.is_finite()) || self.start_angle == self.end_angle {
+            return Err(anyhow!("start_angle and end_angle have to differ"));
+        }
+        for (name, step) in [("major_tick", self.major_tick), ("minor_tick", self.minor_tick), ("label_step", self.label_step)] {
+            if !(step.is_finite() && step > 0.0) {
+                return Err(anyhow!("{} has to be above 0, got {}", name, step));
+            }
+        }
+        if (self.max - self.min) / self.minor_tick > 360.0 {
+            return Err(anyhow!("minor_tick {} gives more than 360 ticks", self.minor_tick));
+        }
+        if self.needle_length > self.radius {
+            return Err(anyhow!("needle_length ({}) is longer than the radius ({})", self.needle_length, self.radius));
+        }
+        Ok(())
+    }
+
+    // Needle angle in radians for `value`. Values outside min..max carry on past the ends.
+    pub fn angle(&self, value: f32) -> f32 {
+        let sweep = (self.end_angle - self.start_angle).to_radians();
+        self.start_angle.to_radians() + (value - self.min) * sweep / (self.max - self.min)
+    }
+
+    pub fn ticks(&self) -> impl Iterator<Item = Tick> + '_ {
+        // whole minor steps only, a range that is not a multiple stops short of max
+        // rather than putting a tick past the end of the sweep
+        let count = ((self.max - self.min) / self.minor_tick + 1e-3).floor() as u32;
+        (0..=count).map(move |i| {
+            let offset = i as f32 * self.minor_tick;
+            let value = self.min + offset;
+            Tick {
+                value,
+                angle: self.angle(value),
+                major: is_multiple(offset, self.major_tick),
+                label: is_multiple(offset, self.label_step),
+            }
+        })
+    }
+}
+
+fn point<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Point, D::Error> {
+    let [x, y] = <[i32; 2]>::deserialize(deserializer)?;
+    Ok(Point::new(x, y))
+}
+
+fn is_multiple(value: f32, step: f32) -> bool {
+    let steps = value / step;
+    (steps - steps.round()).abs() < 1e-3
+}
+
+impl Default for GaugeSpec {
+    // 0 to 120 mi/hr over the top half, as on the original GC9A01 build
+    fn default() -> Self {
+        Self {
+            min: 0.0,
+            max: 120.0,
+            start_angle: 180.0,
+            end_angle: 360.0,
+            major_tick: 20.0,
+            minor_tick: 10.0,
+            label_step: 20.0,
+            radius: 112,
+            center: Point::new(119, 119),
+            needle_length: 92,
+            unit: "mi/hr".to_string(),
+            background: None,
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+
+// Units a reading can come in, and the gauge can be labelled in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpeedUnit {
+    Mph,
+    Kmh,
+    MetersPerSecond,
+    Knots,
+}
+
+impl SpeedUnit {
+    fn meters_per_second(self) -> f32 {
+        match self {
+            SpeedUnit::Mph => 0.44704,
+            SpeedUnit::Kmh => 1.0 / 3.6,
+            SpeedUnit::MetersPerSecond => 1.0,
+            SpeedUnit::Knots => 1852.0 / 3600.0,
+        }
+    }
+}
+
+impl FromStr for SpeedUnit {
+    type Err = anyhow::Error;
+
+    // Case does not matter, so the gauge label ("mi/hr", "km/h") works too
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "mph" | "mi/h" | "mi/hr" => Ok(SpeedUnit::Mph),
+            "km/h" | "km/hr" | "kmh" | "kph" => Ok(SpeedUnit::Kmh),
+            "m/s" | "mps" => Ok(SpeedUnit::MetersPerSecond),
+            "kn" | "kt" | "kts" | "knots" => Ok(SpeedUnit::Knots),
+            _ => Err(anyhow!("unknown unit \"{}\"", s)),
+        }
+    }
+}
+
+impl fmt::Display for SpeedUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            SpeedUnit::Mph => "mph",
+            SpeedUnit::Kmh => "km/h",
+            SpeedUnit::MetersPerSecond => "m/s",
+            SpeedUnit::Knots => "kn",
+        };
+        f.write_str(s)
+    }
+}
+
+// A speed reading as it came in. Readings without a unit are taken to already
+// be in whatever the gauge shows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Speed {
+    pub value: f32,
+    pub unit: Option<SpeedUnit>,
+}
+
+impl Speed {
+    pub fn new(value: f32, unit: SpeedUnit) -> Self {
+        Self { value, unit: Some(unit) }
+    }
+
+    // A bare number, in the gauge's unit
+    pub fn bare(value: f32) -> Self {
+        Self { value, unit: None }
+    }
+
+    // For readings off the wire: only finite, non negative speeds get through
+    pub fn checked(value: f32, unit: Option<SpeedUnit>) -> Result<Self> {
+        if !value.is_finite() {
+            return Err(anyhow!("speed out of range: {}", value));
+        }
+        if value < 0.0 {
+            return Err(anyhow!("negative speed: {}", value));
+        }
+        // -0 would show up as "-0" on the readout
+        let value = if value == 0.0 { 0.0 } else { value };
+        Ok(Self { value, unit })
+    }
+
+    // The value to show on a gauge in `unit`, None when the gauge is not
+    // labelled with a speed unit (a tachometer, say)
+    pub fn in_unit(&self, unit: Option<SpeedUnit>) -> Result<f32> {
+        let value = match (self.unit, unit) {
+            (None, _) => self.value,
+            (Some(from), Some(to)) if from == to => self.value,
+            (Some(from), Some(to)) => self.value * from.meters_per_second() / to.meters_per_second(),
+            (Some(from), None) => return Err(anyhow!("reading in {} but the gauge is not in a speed unit", from)),
+        };
+        // converting up can overflow what an f32 holds
+        if !value.is_finite() {
+            return Err(anyhow!("speed out of range: {} {}", self.value, self.unit.map(|u| u.to_string()).unwrap_or_default()));
+        }
+        Ok(value)
+    }
+}
+
+impl FromStr for Speed {
+    type Err = anyhow::Error;
+
+    // A number with an optional unit after it: "42", "42 mph", "67.6km/h", "18.8 m/s".
+    // Anything that is not a finite, non negative speed is an error.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(anyhow!("no speed given"));
+        }
+        // nothing sensible is this long, and it keeps the error messages short
+        if s.len() > 32 {
+            return Err(anyhow!("speed input too long ({} bytes)", s.len()));
+        }
+
+        // the number runs up to the first space or letter, leaving e/E for exponents
+        let split = s
+            .find(|c: char| c.is_whitespace() || c == '/' || (c.is_alphabetic() && c != 'e' && c != 'E'))
+            .unwrap_or(s.len());
+        let (number, unit) = (&s[..split], s[split..].trim());
+
+        let value = number.parse::<f32>().map_err(|_| anyhow!("not a number: \"{}\"", s))?;
+        let unit = match unit {
+            "" => None,
+            unit => Some(unit.parse::<SpeedUnit>()?),
+        };
+        Speed::checked(value, unit)
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::{anyhow, Result};
+use embedded_graphics::{
+    mono_font::{ascii, MonoFont, MonoTextStyle},
+    pixelcolor::Rgb565,
+    prelude::*,
+    primitives::PrimitiveStyle,
+};
+use serde::{Deserialize, Deserializer};
+
+// Rgb565::new keeps only the low 5/6/5 bits of each channel, so on screen this
+// is #00FC98, which is what a config has to say to get the same colour
+pub const NEON_GREEN: Rgb565 = Rgb565::new(0, 191, 83);
+
+// Colours and fonts of the gauge
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    #[serde(deserialize_with = "color")]
+    pub ring: Rgb565,
+    #[serde(deserialize_with = "color")]
+    pub ticks: Rgb565,
+    #[serde(deserialize_with = "color")]
+    pub needle: Rgb565,
+    #[serde(deserialize_with = "color")]
+    pub text: Rgb565,
+    // status line for bad input and the like
+    #[serde(deserialize_with = "color")]
+    pub warning: Rgb565,
+    // needle and readout while there is no signal
+    #[serde(deserialize_with = "color")]
+    pub lost: Rgb565,
+    #[serde(deserialize_with = "font")]
+    pub label_font: &'static MonoFont<'static>,
+    #[serde(deserialize_with = "font")]
+    pub readout_font: &'static MonoFont<'static>,
+    #[serde(deserialize_with = "font")]
+    pub unit_font: &'static MonoFont<'static>,
+}
+
+impl Theme {
+    pub fn circle_style(&self) -> PrimitiveStyle<Rgb565> {
+        PrimitiveStyle::with_stroke(self.ring, 4)
+    }
+
+    pub fn tick_style(&self, major: bool) -> PrimitiveStyle<Rgb565> {
+        PrimitiveStyle::with_stroke(self.ticks, if major { 3 } else { 1 })
+    }
+
+    pub fn needle_style(&self) -> PrimitiveStyle<Rgb565> {
+        PrimitiveStyle::with_stroke(self.needle, 2)
+    }
+
+    pub fn text_style(&self) -> MonoTextStyle<'static, Rgb565> {
+        MonoTextStyle::new(self.label_font, self.text)
+    }
+
+    pub fn speed_text_style(&self) -> MonoTextStyle<'static, Rgb565> {
+        MonoTextStyle::new(self.readout_font, self.text)
+    }
+
+    pub fn unit_text_style(&self) -> MonoTextStyle<'static, Rgb565> {
+        MonoTextStyle::new(self.unit_font, self.text)
+    }
+
+    pub fn status_text_style(&self) -> MonoTextStyle<'static, Rgb565> {
+        MonoTextStyle::new(self.label_font, self.warning)
+    }
+
+    pub fn lost_needle_style(&self) -> PrimitiveStyle<Rgb565> {
+        PrimitiveStyle::with_stroke(self.lost, 2)
+    }
+
+    pub fn lost_text_style(&self) -> MonoTextStyle<'static, Rgb565> {
+        MonoTextStyle::new(self.readout_font, self.lost)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            ring: NEON_GREEN,
+            ticks: NEON_GREEN,
+            needle: Rgb565::RED,
+            text: Rgb565::WHITE,
+            warning: Rgb565::YELLOW,
+            // #808080
+            lost: Rgb565::new(16, 32, 16),
+            label_font: &ascii::FONT_6X13_BOLD,
+            readout_font: &profont::PROFONT_24_POINT,
+            unit_font: &ascii::FONT_10X20,
+        }
+    }
+}
+
+// Parses "#RRGGBB", dropping the low bits that do not fit in RGB565
+pub fn parse_color(s: &str) -> Result<Rgb565> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 {
+        return Err(anyhow!("Expected a colour like \"#00BF53\", got \"{}\"", s));
+    }
+    let rgb = u32::from_str_radix(hex, 16).map_err(|_| anyhow!("Expected a colour like \"#00BF53\", got \"{}\"", s))?;
+    let (r, g, b) = ((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8);
+    Ok(Rgb565::new(r >> 3, g >> 2, b >> 3))
+}
+
+// Fonts that can be picked by name in the config
+pub fn font_by_name(name: &str) -> Result<&'static MonoFont<'static>> {
+    let font = match name {
+        "6x10" => &ascii::FONT_6X10,
+        "6x13" => &ascii::FONT_6X13,
+        "6x13_bold" => &ascii::FONT_6X13_BOLD,
+        "7x13" => &ascii::FONT_7X13,
+        "7x13_bold" => &ascii::FONT_7X13_BOLD,
+        "8x13" => &ascii::FONT_8X13,
+        "8x13_bold" => &ascii::FONT_8X13_BOLD,
+        "9x15" => &ascii::FONT_9X15,
+        "9x15_bold" => &ascii::FONT_9X15_BOLD,
+        "9x18" => &ascii::FONT_9X18,
+        "9x18_bold" => &ascii::FONT_9X18_BOLD,
+        "10x20" => &ascii::FONT_10X20,
+        "profont_12" => &profont::PROFONT_12_POINT,
+        "profont_14" => &profont::PROFONT_14_POINT,
+        "profont_18" => &profont::PROFONT_18_POINT,
+        "profont_24" => &profont::PROFONT_24_POINT,
+        _ => return Err(anyhow!("Unknown font \"{}\"", name)),
+    };
+    Ok(font)
+}
+
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rgb565, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    parse_color(&s).map_err(serde::de::Error::custom)
+}
+
+fn font<'de, D: Deserializer<'de>>(deserializer: D) -> Result<&'static MonoFont<'static>, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    font_by_name(&s).map_err(serde::de::Error::custom)
+}
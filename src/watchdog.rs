@@ -0,0 +1,33 @@
+use std::time::{Duration, Instant};
+
+// Notices when the speed stops coming. Every good sample feeds it; once there
+// has been none for `timeout` the signal is lost, until the next one.
+pub struct Watchdog {
+    // None never goes off
+    timeout: Option<Duration>,
+    last: Instant,
+    lost: bool,
+}
+
+impl Watchdog {
+    // Counts from `now`, so a source that never says anything is lost too
+    pub fn new(timeout: Option<Duration>, now: Instant) -> Self {
+        Self { timeout, last: now, lost: false }
+    }
+
+    pub fn feed(&mut self, now: Instant) {
+        self.last = now;
+    }
+
+    pub fn lost(&self) -> bool {
+        self.lost
+    }
+
+    // Whether the signal was lost or came back since the last check
+    pub fn check(&mut self, now: Instant) -> bool {
+        let lost = self.timeout.is_some_and(|timeout| now.saturating_duration_since(self.last) >= timeout);
+        let changed = lost != self.lost;
+        self.lost = lost;
+        changed
+    }
+}
@@ -0,0 +1,116 @@
+// The needle's spring, stepped through made up time
+
+use std::time::Duration;
+
+use speedometer::animation::{AnimationConfig, Model, Needle};
+
+const FRAME: Duration = Duration::from_millis(33);
+
+fn spring(damping: f32) -> AnimationConfig {
+    AnimationConfig { damping, ..AnimationConfig::default() }
+}
+
+// Positions frame by frame for two seconds
+fn frames(needle: &mut Needle) -> Vec<f32> {
+    (0..60)
+        .map(|_| {
+            needle.advance(FRAME);
+            needle.position()
+        })
+        .collect()
+}
+
+#[test]
+fn critically_damped_gets_there_without_overshooting() {
+    let mut needle = Needle::new(&spring(1.0), 0.0, 120.0);
+    needle.set_target(60.0);
+    let frames = frames(&mut needle);
+
+    assert!(frames.windows(2).all(|w| w[0] <= w[1]), "went back: {:?}", frames);
+    assert!(frames.iter().all(|&p| p <= 60.0));
+    // well on its way after a frame or two, there within a second
+    assert!(frames[0] > 0.0 && frames[0] < 10.0, "{}", frames[0]);
+    assert!((frames[30] - 60.0).abs() < 0.5, "{}", frames[30]);
+    assert_eq!(needle.position(), 60.0);
+    assert!(!needle.moving());
+}
+
+#[test]
+fn under_damped_overshoots_a_little_and_settles() {
+    let mut needle = Needle::new(&spring(0.6), 0.0, 120.0);
+    needle.set_target(60.0);
+    let frames = frames(&mut needle);
+
+    let peak = frames.iter().cloned().fold(f32::MIN, f32::max);
+    assert!(peak > 60.5 && peak < 66.0, "peak {}", peak);
+    assert!(!needle.moving());
+    assert_eq!(needle.position(), 60.0);
+}
+
+#[test]
+fn over_damped_creeps_in_and_settles() {
+    // heavily damped, slow and stiff, all past where a plain 4 ms step blows up
+    for (damping, frequency_hz) in [(10.0, 1.5), (3.0, 20.0), (10.0, 20.0)] {
+        let config = AnimationConfig { damping, frequency_hz, ..AnimationConfig::default() };
+        config.validate().unwrap();
+        let mut needle = Needle::new(&config, 0.0, 120.0);
+        needle.set_target(60.0);
+
+        let mut last = 0.0;
+        for _ in 0..(60 * 30) {
+            needle.advance(FRAME);
+            let position = needle.position();
+            assert!(position.is_finite() && position >= last && position <= 60.0, "{} after {}", position, last);
+            last = position;
+        }
+        assert!(!needle.moving(), "damping {} at {} Hz still moving at {}", damping, frequency_hz, needle.position());
+        assert_eq!(needle.position(), 60.0);
+    }
+}
+
+#[test]
+fn the_same_time_in_any_slices_gives_the_same_positions() {
+    let mut whole = Needle::new(&spring(0.7), 0.0, 120.0);
+    let mut sliced = Needle::new(&spring(0.7), 0.0, 120.0);
+    whole.set_target(80.0);
+    sliced.set_target(80.0);
+
+    whole.advance(Duration::from_millis(200));
+    for _ in 0..200 {
+        sliced.advance(Duration::from_millis(1));
+    }
+    assert_eq!(whole.position(), sliced.position());
+
+    // uneven frames with a change of speed on the way
+    whole.set_target(20.0);
+    sliced.set_target(20.0);
+    whole.advance(Duration::from_millis(150));
+    for ms in [7, 33, 3, 50, 57] {
+        sliced.advance(Duration::from_millis(ms));
+    }
+    assert_eq!(whole.position(), sliced.position());
+}
+
+#[test]
+fn a_stall_does_not_skip_to_the_end() {
+    let mut needle = Needle::new(&spring(1.0), 0.0, 120.0);
+    needle.set_target(100.0);
+    needle.advance(Duration::from_secs(10));
+    assert!(needle.moving());
+    assert!(needle.position() < 100.0);
+}
+
+#[test]
+fn snap_and_settle_jump_straight_there() {
+    let mut needle = Needle::new(&AnimationConfig { model: Model::Snap, ..AnimationConfig::default() }, 0.0, 120.0);
+    needle.set_target(42.0);
+    assert_eq!(needle.position(), 42.0);
+    assert!(!needle.moving());
+
+    let mut needle = Needle::new(&spring(1.0), 0.0, 120.0);
+    needle.set_target(42.0);
+    needle.advance(FRAME);
+    needle.settle();
+    assert_eq!(needle.position(), 42.0);
+    assert!(!needle.moving());
+}
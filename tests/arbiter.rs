@@ -0,0 +1,128 @@
+// Picking between sources, on made up readings and times
+
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use speedometer::{
+    arbiter::Arbiter,
+    config::{FailoverSource, SourceKind},
+    Speed,
+};
+
+const OBD: usize = 0;
+const GPS: usize = 1;
+const FILE: usize = 2;
+
+// OBD first, stale after 1 s, then gpsd after 2 s, then the file which never is
+fn arbiter() -> Arbiter {
+    Arbiter::new(&[
+        FailoverSource { source: SourceKind::Obd, stale_ms: 1000 },
+        FailoverSource { source: SourceKind::Gpsd, stale_ms: 2000 },
+        FailoverSource { source: SourceKind::File, stale_ms: 0 },
+    ])
+}
+
+fn ms(start: Instant, ms: u64) -> Instant {
+    start + Duration::from_millis(ms)
+}
+
+fn shown(result: Option<anyhow::Result<Speed>>) -> Option<f32> {
+    result.map(|r| r.unwrap().value)
+}
+
+#[test]
+fn the_first_fresh_source_wins() {
+    let mut arbiter = arbiter();
+    let t = Instant::now();
+
+    // only the file so far
+    assert_eq!(shown(arbiter.reading(FILE, Ok(Speed::bare(10.0)), t)), Some(10.0));
+    assert_eq!(arbiter.active(), Some(SourceKind::File));
+    // then gpsd, then OBD take over as they come up
+    assert_eq!(shown(arbiter.reading(GPS, Ok(Speed::bare(20.0)), ms(t, 10))), Some(20.0));
+    assert_eq!(shown(arbiter.reading(OBD, Ok(Speed::bare(30.0)), ms(t, 20))), Some(30.0));
+    assert_eq!(arbiter.badge().as_deref(), Some("OBD"));
+
+    // the others keep going, but are not shown
+    assert_eq!(shown(arbiter.reading(GPS, Ok(Speed::bare(21.0)), ms(t, 30))), None);
+    assert_eq!(shown(arbiter.reading(FILE, Ok(Speed::bare(11.0)), ms(t, 40))), None);
+    assert_eq!(shown(arbiter.reading(OBD, Ok(Speed::bare(31.0)), ms(t, 50))), Some(31.0));
+}
+
+#[test]
+fn fails_over_when_stale_and_back_when_fresh() {
+    let mut arbiter = arbiter();
+    let t = Instant::now();
+    arbiter.reading(FILE, Ok(Speed::bare(10.0)), t);
+    arbiter.reading(GPS, Ok(Speed::bare(20.0)), t);
+    arbiter.reading(OBD, Ok(Speed::bare(30.0)), t);
+
+    // nothing from OBD for a second, gpsd takes over with what it had
+    assert_eq!(shown(arbiter.check(ms(t, 999))), None);
+    assert_eq!(shown(arbiter.check(ms(t, 1000))), Some(20.0));
+    assert_eq!(arbiter.active(), Some(SourceKind::Gpsd));
+    assert_eq!(shown(arbiter.check(ms(t, 1500))), None);
+
+    // gpsd goes stale too, the file never does
+    assert_eq!(shown(arbiter.check(ms(t, 2000))), Some(10.0));
+    assert_eq!(arbiter.badge().as_deref(), Some("FILE"));
+
+    // OBD is back
+    assert_eq!(shown(arbiter.reading(OBD, Ok(Speed::bare(33.0)), ms(t, 2500))), Some(33.0));
+    assert_eq!(arbiter.active(), Some(SourceKind::Obd));
+}
+
+#[test]
+fn a_problem_hands_over_straight_away() {
+    let mut arbiter = arbiter();
+    let t = Instant::now();
+    arbiter.reading(GPS, Ok(Speed::bare(20.0)), t);
+    arbiter.reading(OBD, Ok(Speed::bare(30.0)), t);
+
+    assert_eq!(shown(arbiter.reading(OBD, Err(anyhow!("no data from the car")), ms(t, 10))), Some(20.0));
+    assert_eq!(arbiter.active(), Some(SourceKind::Gpsd));
+    // a problem with a source that is not shown changes nothing
+    assert!(arbiter.reading(FILE, Err(anyhow!("not a number")), ms(t, 20)).is_none());
+
+    // with nothing left, the problem is what is shown
+    let last = arbiter.reading(GPS, Err(anyhow!("no fix")), ms(t, 30)).unwrap();
+    assert_eq!(last.unwrap_err().to_string(), "no fix");
+    assert_eq!(arbiter.active(), None);
+    assert_eq!(arbiter.badge(), None);
+}
+
+#[test]
+fn nothing_fresh_is_an_error() {
+    let mut arbiter = arbiter();
+    let t = Instant::now();
+    arbiter.reading(OBD, Ok(Speed::bare(30.0)), t);
+    let shown = arbiter.check(ms(t, 1000)).unwrap();
+    assert_eq!(shown.unwrap_err().to_string(), "no speed from any source");
+    assert!(arbiter.check(ms(t, 5000)).is_none());
+}
+
+#[test]
+fn ended_sources_are_left_out() {
+    let mut arbiter = arbiter();
+    let t = Instant::now();
+    arbiter.reading(GPS, Ok(Speed::bare(20.0)), t);
+    arbiter.reading(OBD, Ok(Speed::bare(30.0)), t);
+
+    assert_eq!(shown(arbiter.ended(OBD, ms(t, 10))), Some(20.0));
+    assert!(arbiter.ended(FILE, ms(t, 20)).is_none());
+    assert!(!arbiter.all_ended());
+    arbiter.ended(GPS, ms(t, 30));
+    assert!(arbiter.all_ended());
+}
+
+#[test]
+fn a_single_source_passes_everything_through() {
+    let mut arbiter = Arbiter::single(SourceKind::File);
+    let t = Instant::now();
+    assert_eq!(shown(arbiter.reading(0, Ok(Speed::bare(5.0)), t)), Some(5.0));
+    assert!(arbiter.reading(0, Err(anyhow!("not a number")), ms(t, 10)).unwrap().is_err());
+    assert_eq!(shown(arbiter.reading(0, Ok(Speed::bare(6.0)), ms(t, 20))), Some(6.0));
+    // never stale, and nothing to badge
+    assert!(arbiter.check(ms(t, 60_000)).is_none());
+    assert_eq!(arbiter.badge(), None);
+}
@@ -0,0 +1,171 @@
+// Signals decoded out of CAN frames, defined by hand and read from a DBC file,
+// and on a real vcan0 when there is one
+
+use speedometer::{
+    signal::{self, ByteOrder, Signal},
+    source::CanConfig,
+};
+
+fn signal(start_bit: u16, length: u16, byte_order: ByteOrder) -> Signal {
+    Signal {
+        name: "speed".to_string(),
+        id: 0x1A0,
+        extended: false,
+        start_bit,
+        length,
+        byte_order,
+        signed: false,
+        scale: 1.0,
+        offset: 0.0,
+        unit: String::new(),
+    }
+}
+
+#[test]
+fn decodes_little_endian_signals() {
+    let data = [0x00, 0xCD, 0xAB, 0x00];
+    assert_eq!(signal(8, 16, ByteOrder::LittleEndian).decode(&data), Some(0xABCD as f64));
+    assert_eq!(signal(8, 12, ByteOrder::LittleEndian).decode(&data), Some(0xBCD as f64));
+    assert_eq!(signal(12, 4, ByteOrder::LittleEndian).decode(&data), Some(0xC as f64));
+
+    // 30.00 km/h in hundredths
+    let speed = Signal { scale: 0.01, unit: "km/h".to_string(), ..signal(0, 16, ByteOrder::LittleEndian) };
+    assert!((speed.decode(&[0xB8, 0x0B]).unwrap() - 30.0).abs() < 1e-9);
+}
+
+#[test]
+fn decodes_big_endian_signals() {
+    let data = [0x12, 0x34, 0xAB, 0x00];
+    assert_eq!(signal(7, 16, ByteOrder::BigEndian).decode(&data), Some(0x1234 as f64));
+    // the low nibble of byte 2, its most significant bit is bit 19
+    assert_eq!(signal(19, 4, ByteOrder::BigEndian).decode(&data), Some(0xB as f64));
+    // across bytes: the low nibble of byte 0 and all of byte 1
+    assert_eq!(signal(3, 12, ByteOrder::BigEndian).decode(&data), Some(0x234 as f64));
+}
+
+#[test]
+fn signed_scaled_and_offset() {
+    let temp = Signal { signed: true, scale: 0.5, offset: -10.0, ..signal(0, 8, ByteOrder::LittleEndian) };
+    assert_eq!(temp.decode(&[0xFE]), Some(-11.0));
+    assert_eq!(temp.decode(&[0x14]), Some(0.0));
+
+    let full = Signal { signed: true, ..signal(0, 64, ByteOrder::LittleEndian) };
+    assert_eq!(full.decode(&(-5i64).to_le_bytes()), Some(-5.0));
+}
+
+#[test]
+fn short_frames_and_bad_signals() {
+    assert_eq!(signal(8, 16, ByteOrder::LittleEndian).decode(&[0x00, 0x01]), None);
+    assert_eq!(signal(7, 16, ByteOrder::BigEndian).decode(&[0x12]), None);
+
+    assert!(signal(0, 0, ByteOrder::LittleEndian).validate().is_err());
+    assert!(signal(60, 8, ByteOrder::LittleEndian).validate().is_err());
+    assert!(signal(56, 16, ByteOrder::BigEndian).validate().is_err());
+    // far out of range, which must not overflow on the way to saying so
+    assert!(signal(65535, 8, ByteOrder::LittleEndian).validate().is_err());
+    assert!(signal(65535, 8, ByteOrder::BigEndian).validate().is_err());
+    assert!(signal(0, 65535, ByteOrder::LittleEndian).validate().is_err());
+    assert!(Signal { id: 0x800, ..signal(0, 8, ByteOrder::LittleEndian) }.validate().is_err());
+    assert!(Signal { id: 0x800, extended: true, ..signal(0, 8, ByteOrder::LittleEndian) }.validate().is_ok());
+}
+
+const DBC: &str = r#"
+VERSION ""
+
+BU_: ABS Dashboard
+
+BO_ 416 WheelSpeeds: 8 ABS
+ SG_ WheelSpeedFL : 7|16@0+ (0.01,0) [0|300] "km/h" Dashboard
+ SG_ WheelSpeedFR : 23|16@0+ (0.01,0) [0|300] "km/h" Dashboard
+ SG_ YawRate : 32|16@1- (0.1,-5) [-200|200] "deg/s" Dashboard
+
+BO_ 2364540158 Engine: 8 ECU
+ SG_ Mux M : 0|8@1+ (1,0) [0|255] "" Dashboard
+ SG_ Rpm m1 : 8|16@1+ (0.25,0) [0|16383] "rpm" Dashboard
+ SG_ Coolant : 56|8@1+ (1,-40) [-40|215] "degC" Dashboard
+
+CM_ SG_ 416 WheelSpeedFL "Front left";
+"#;
+
+#[test]
+fn reads_signals_from_a_dbc() {
+    let signals = signal::parse_dbc(DBC).unwrap();
+    let names: Vec<&str> = signals.iter().map(|s| s.name.as_str()).collect();
+    // Rpm is multiplexed and left out
+    assert_eq!(names, ["WheelSpeedFL", "WheelSpeedFR", "YawRate", "Mux", "Coolant"]);
+
+    let fl = &signals[0];
+    assert_eq!((fl.id, fl.extended, fl.start_bit, fl.length), (416, false, 7, 16));
+    assert_eq!((fl.byte_order, fl.signed, fl.scale, fl.unit.as_str()), (ByteOrder::BigEndian, false, 0.01, "km/h"));
+    assert_eq!(fl.decode(&[0x0B, 0xB8, 0, 0, 0, 0, 0, 0]).map(|v| (v * 100.0).round()), Some(3000.0));
+
+    let yaw = &signals[2];
+    assert_eq!((yaw.byte_order, yaw.signed, yaw.offset), (ByteOrder::LittleEndian, true, -5.0));
+
+    let coolant = &signals[4];
+    assert_eq!((coolant.id, coolant.extended), (0x0CF0_04FE, true));
+}
+
+#[test]
+fn bad_dbc_lines_say_where() {
+    let err = signal::parse_dbc("BO_ 416 A: 8 X\n SG_ S : 7|16@2+ (1,0) [0|1] \"\" X\n").unwrap_err();
+    assert_eq!(format!("{:#}", err), "line 2: S: bad byte order \"2+\"");
+    assert!(signal::parse_dbc(" SG_ S : 0|8@1+ (1,0) [0|1] \"\" X\n").is_err());
+    let err = signal::parse_dbc("BO_ 416 A: 8 X\n SG_ s : 65535|8@1+ (1,0) [0|1] \"\" X\n").unwrap_err();
+    assert_eq!(format!("{:#}", err), "line 2: s: start bit has to be 0 to 63, got 65535");
+    let err = signal::parse_dbc("BO_ 416 A: 8 X\n SG_ s : 0|65535@0+ (1,0) [0|1] \"\" X\n").unwrap_err();
+    assert_eq!(format!("{:#}", err), "line 2: s: length has to be 1 to 64 bits, got 65535");
+}
+
+#[test]
+fn config_needs_the_speed_signal() {
+    let config: CanConfig = toml::from_str(
+        r#"
+        interface = "vcan0"
+        speed_signal = "wheel_speed"
+
+        [[signals]]
+        name = "wheel_speed"
+        id = 0x1A0
+        start_bit = 0
+        length = 16
+        scale = 0.01
+        unit = "km/h"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.load_signals().unwrap()[0].byte_order, ByteOrder::LittleEndian);
+
+    let missing = CanConfig { speed_signal: "speed".to_string(), ..config.clone() };
+    assert!(missing.load_signals().is_err());
+
+    let mut rpm = config.clone();
+    rpm.signals[0].unit = "rpm".to_string();
+    assert!(rpm.load_signals().is_err());
+}
+
+// Needs `ip link add dev vcan0 type vcan && ip link set up vcan0`, skipped without
+#[cfg(feature = "can")]
+#[test]
+fn reads_speed_from_vcan0() {
+    use socketcan::{CanFrame, CanSocket, EmbeddedFrame, Socket, StandardId};
+    use speedometer::{source::CanSource, Speed, SpeedSource, SpeedUnit};
+
+    if !std::path::Path::new("/sys/class/net/vcan0").exists() {
+        eprintln!("no vcan0, skipping");
+        return;
+    }
+    let speed = Signal { scale: 0.01, unit: "km/h".to_string(), ..signal(0, 16, ByteOrder::LittleEndian) };
+    let rpm = Signal { name: "rpm".to_string(), start_bit: 16, scale: 0.25, unit: "rpm".to_string(), ..speed.clone() };
+    let config = CanConfig { interface: "vcan0".to_string(), signals: vec![speed, rpm], ..CanConfig::default() };
+    let mut source = CanSource::open(&config).unwrap();
+
+    let sender = CanSocket::open("vcan0").unwrap();
+    let other = CanFrame::new(StandardId::new(0x123).unwrap(), &[0xFF; 8]).unwrap();
+    let frame = CanFrame::new(StandardId::new(0x1A0).unwrap(), &[0xB8, 0x0B, 0xE0, 0x2E]).unwrap();
+    sender.write_frame(&other).unwrap();
+    sender.write_frame(&frame).unwrap();
+
+    assert_eq!(source.next_speed().unwrap(), Speed::new(30.0, SpeedUnit::Kmh));
+    assert_eq!(source.channels().get("rpm"), Some(&3000.0));
+}
@@ -0,0 +1,75 @@
+// Runs the speedometer binary the way it is used from a shell, against the
+// example config
+
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("speedometer-cli-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn speedometer(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_speedometer"))
+        .args(["--config", "speedometer.toml"])
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+fn render(out: &PathBuf, extra: &[&str]) -> Vec<u8> {
+    let output = speedometer(&[&["render", "--speed", "42", "--out", out.to_str().unwrap()], extra].concat());
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    fs::read(out).unwrap()
+}
+
+#[test]
+fn render_writes_a_240x240_png() {
+    let dir = temp_dir("png");
+    let png = render(&dir.join("x.png"), &[]);
+
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(u32::from_be_bytes(png[16..20].try_into().unwrap()), 240);
+    assert_eq!(u32::from_be_bytes(png[20..24].try_into().unwrap()), 240);
+}
+
+#[test]
+fn unit_overrides_the_config() {
+    let dir = temp_dir("unit");
+    let config = render(&dir.join("config.raw"), &[]);
+    let kmh = render(&dir.join("kmh.raw"), &["--unit", "km/h"]);
+    // the example config says mi/hr already
+    let mph = render(&dir.join("mph.raw"), &["--unit", "mi/hr"]);
+
+    assert_ne!(config, kmh);
+    assert_eq!(config, mph);
+}
+
+#[test]
+fn brightness_overrides_the_config() {
+    // stdin is closed straight away, so the run ends after setting up
+    let output = speedometer(&["--backend", "headless", "--stdin", "-v", "--brightness", "7"]);
+    assert!(output.status.success());
+    let log = String::from_utf8_lossy(&output.stderr);
+    assert!(log.contains("Backlight at 7"), "{}", log);
+    assert!(!log.contains("Backlight at 255"), "{}", log);
+}
+
+#[test]
+fn demo_rejects_a_step_that_goes_nowhere() {
+    let output = speedometer(&["--backend", "headless", "demo", "--step", "0"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("has to be above 0"));
+
+    // above 0, but far under a millionth of the scale
+    let output = speedometer(&["--backend", "headless", "demo", "--step", "1e-9"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("too small to move the needle"));
+}
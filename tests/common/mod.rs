@@ -0,0 +1,20 @@
+// Shared by the source tests
+
+use std::{
+    sync::mpsc::{channel, Receiver},
+    thread,
+};
+
+use speedometer::{Speed, SpeedSource};
+
+// Runs a source on its own thread as the speedometer does, readings (or error
+// messages) come out of the receiver
+pub fn readings<S: SpeedSource + Send + 'static>(mut source: S) -> Receiver<Result<Speed, String>> {
+    let (tx, rx) = channel();
+    thread::spawn(move || loop {
+        if tx.send(source.next_speed().map_err(|e| format!("{:#}", e))).is_err() {
+            break;
+        }
+    });
+    rx
+}
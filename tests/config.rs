@@ -0,0 +1,114 @@
+// Loading and validating the TOML config
+
+use std::path::PathBuf;
+
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+use speedometer::{
+    config::{FailoverSource, SourceKind},
+    filter::FilterConfig,
+    theme::NEON_GREEN,
+    Config, GaugeSpec, Theme,
+};
+
+fn parse(s: &str) -> anyhow::Result<Config> {
+    let config: Config = toml::from_str(s)?;
+    config.validate()?;
+    Ok(config)
+}
+
+#[test]
+fn example_config_is_the_default() {
+    let config = Config::load(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("speedometer.toml")).unwrap();
+    let default = Config::default();
+
+    assert_eq!(config.display, default.display);
+    assert_eq!(config.input, default.input);
+    assert_eq!(config.gauge, GaugeSpec::default());
+    assert_eq!(config.theme.ring, NEON_GREEN);
+    assert_eq!(config.theme.ticks, Theme::default().ticks);
+    assert_eq!(config.theme.needle, Rgb565::RED);
+    assert_eq!(config.animation, default.animation);
+    assert_eq!(config.filter, default.filter);
+}
+
+#[test]
+fn empty_config_is_the_default() {
+    let config = parse("").unwrap();
+    assert_eq!(config.display, Config::default().display);
+    assert_eq!(config.gauge, GaugeSpec::default());
+}
+
+#[test]
+fn partial_sections_keep_the_other_defaults() {
+    let config = parse("[display]\ndc_pin = 24\n[gauge]\nmax = 200.0\ncenter = [120, 118]").unwrap();
+    assert_eq!(config.display.dc_pin, 24);
+    assert_eq!(config.display.cs_pin, 8);
+    assert_eq!(config.gauge.max, 200.0);
+    assert_eq!(config.gauge.center.y, 118);
+    assert_eq!(config.gauge.unit, "mi/hr");
+}
+
+#[test]
+fn rejects_bad_settings() {
+    for (toml, expected) in [
+        ("[display]\ncs_pin = 25", "cs_pin and dc_pin are both GPIO 25"),
+        ("[display]\nreset_pin = 40", "reset_pin has to be a GPIO from 0 to 27"),
+        ("[display]\nbrightness = 300", "brightness"),
+        ("[display]\nreset = 4", "unknown field `reset`"),
+        ("[theme]\nneedle = \"red\"", "Expected a colour"),
+        ("[theme]\nunit_font = \"comic_sans\"", "Unknown font"),
+        ("[gauge]\nmin = 10.0\nmax = 0.0", "has to be above min"),
+        ("[[input.failover]]\nsource = \"obd\"\n[[input.failover]]\nsource = \"obd\"", "obd more than once"),
+        ("[[input.failover]]\nstale_ms = 100", "missing field `source`"),
+        ("[animation]\nmodel = \"bouncy\"", "unknown variant `bouncy`"),
+        ("[animation]\nfps = 0", "fps has to be from 1 to 60"),
+        ("[[filter]]\nkind = \"median\"\nwindow = 0", "window has to be from 1 to 100"),
+        ("[[filter]]\nkind = \"ema\"\nalpha = 1.5", "alpha has to be above 0"),
+        ("[[filter]]\nkind = \"ema\"", "missing field `alpha`"),
+        ("[[filter]]\nkind = \"kalman\"\nprocess_noise = inf\nmeasurement_noise = 4.0", "have to be numbers above 0"),
+        ("[[filter]]\nkind = \"kalman\"\nprocess_noise = 1.0\nmeasurement_noise = 0.0", "have to be numbers above 0"),
+        ("[[filter]]\nkind = \"lowpass\"", "unknown variant `lowpass`"),
+    ] {
+        let err = format!("{:?}", parse(toml).unwrap_err());
+        assert!(err.contains(expected), "{:?} gave {}", toml, err);
+    }
+}
+
+#[test]
+fn failover_sources_in_order() {
+    let config = parse("[input]\nsource = \"udp\"").unwrap();
+    assert_eq!(config.input.sources(), [FailoverSource { source: SourceKind::Udp, stale_ms: 0 }]);
+
+    let toml = "[[input.failover]]\nsource = \"obd\"\nstale_ms = 500\n[[input.failover]]\nsource = \"gpsd\"\n\
+                [[input.failover]]\nsource = \"file\"\nstale_ms = 0";
+    let config = parse(toml).unwrap();
+    let sources: Vec<_> = config.input.sources().iter().map(|s| (s.source, s.stale_ms)).collect();
+    assert_eq!(sources, [(SourceKind::Obd, 500), (SourceKind::Gpsd, 2000), (SourceKind::File, 0)]);
+}
+
+#[test]
+fn pulse_pin_has_to_be_free() {
+    let pulse = |extra: &str| parse(&format!("[input]\nsource = \"pulse\"\n[input.pulse]\n{}", extra));
+    for (toml, expected) in [
+        ("pin = 25", "pin 25 is already wired to the display, as dc_pin"),
+        ("pin = 18", "pin 18 is already wired to the display, as the backlight PWM"),
+        ("pin = 10", "pin 10 is already wired to the display, as SPI MOSI"),
+        ("pin = 11", "pin 11 is already wired to the display, as SPI SCLK"),
+    ] {
+        let err = format!("{:?}", pulse(toml).unwrap_err());
+        assert!(err.contains(expected), "{:?} gave {}", toml, err);
+    }
+    assert!(pulse("pin = 17").is_ok());
+    // the other PWM channel frees 18 and takes 19
+    assert!(parse("[display]\npwm_channel = 1\n[input]\nsource = \"pulse\"\n[input.pulse]\npin = 18").is_ok());
+    assert!(parse("[display]\npwm_channel = 1\n[input]\nsource = \"pulse\"\n[input.pulse]\npin = 19").is_err());
+    // and a pin the display uses is fine when the pulse input is not
+    assert!(parse("[input.pulse]\npin = 18").is_ok());
+}
+
+#[test]
+fn filters_in_order() {
+    let config = parse("[[filter]]\nkind = \"median\"\nwindow = 5\n[[filter]]\nkind = \"rate_limit\"\nmax_per_s = 20.0")
+        .unwrap();
+    assert_eq!(config.filter, vec![FilterConfig::Median { window: 5 }, FilterConfig::RateLimit { max_per_s: 20.0 }]);
+}
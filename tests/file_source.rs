@@ -0,0 +1,156 @@
+// Follows a speed file through the ways producers write it: in place, by
+// renaming a temp file over it, by deleting and recreating it, and with the
+// whole directory replaced.
+
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver},
+    thread,
+    time::Duration,
+};
+
+use speedometer::{
+    source::{FileSource, WatchMode},
+    Speed, SpeedSource,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("speedometer-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Runs the source on a thread so a missed event fails the test instead of hanging it
+fn follow(path: &Path, mode: WatchMode) -> Receiver<Speed> {
+    let mut source = FileSource::with_mode(path, mode).unwrap();
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        while let Ok(speed) = source.next_speed() {
+            if tx.send(speed).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+fn next(rx: &Receiver<Speed>) -> f32 {
+    rx.recv_timeout(TIMEOUT).expect("no reading").value
+}
+
+// Readings up to and including `want`, skipping repeats of earlier ones
+fn wait_for(rx: &Receiver<Speed>, want: f32) {
+    loop {
+        if next(rx) == want {
+            return;
+        }
+    }
+}
+
+fn write_atomic(path: &Path, s: &str) {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, s).unwrap();
+    fs::rename(&tmp, path).unwrap();
+}
+
+#[test]
+fn reads_what_is_there_at_start() {
+    let dir = temp_dir("start");
+    let path = dir.join("speed.txt");
+    fs::write(&path, "17\n").unwrap();
+
+    let rx = follow(&path, WatchMode::Modify);
+    assert_eq!(next(&rx), 17.0);
+}
+
+#[test]
+fn follows_writes_in_place() {
+    let dir = temp_dir("in-place");
+    let path = dir.join("speed.txt");
+
+    let rx = follow(&path, WatchMode::Modify);
+    for speed in [10, 20, 30] {
+        fs::write(&path, format!("{}\n", speed)).unwrap();
+        wait_for(&rx, speed as f32);
+    }
+}
+
+#[test]
+fn follows_atomic_renames() {
+    let dir = temp_dir("rename");
+    let path = dir.join("speed.txt");
+    fs::write(&path, "1\n").unwrap();
+
+    for mode in [WatchMode::Modify, WatchMode::CloseWrite] {
+        if mode == WatchMode::CloseWrite && !cfg!(target_os = "linux") {
+            continue;
+        }
+        let rx = follow(&path, mode);
+        for speed in [42, 43, 44] {
+            write_atomic(&path, &format!("{}\n", speed));
+            wait_for(&rx, speed as f32);
+        }
+    }
+}
+
+#[test]
+fn survives_delete_and_recreate() {
+    let dir = temp_dir("recreate");
+    let path = dir.join("speed.txt");
+    fs::write(&path, "5\n").unwrap();
+
+    let rx = follow(&path, WatchMode::Modify);
+    assert_eq!(next(&rx), 5.0);
+
+    fs::remove_file(&path).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    fs::write(&path, "6\n").unwrap();
+    wait_for(&rx, 6.0);
+}
+
+#[test]
+fn rearms_when_the_directory_is_replaced() {
+    let dir = temp_dir("rearm");
+    let path = dir.join("speed.txt");
+    fs::write(&path, "7\n").unwrap();
+
+    let rx = follow(&path, WatchMode::Modify);
+    assert_eq!(next(&rx), 7.0);
+
+    fs::remove_dir_all(&dir).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(&path, "8\n").unwrap();
+    wait_for(&rx, 8.0);
+
+    // and keeps following it
+    fs::write(&path, "9\n").unwrap();
+    wait_for(&rx, 9.0);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn close_write_only_reads_finished_writes() {
+    let dir = temp_dir("close-write");
+    let path = dir.join("speed.txt");
+    fs::write(&path, "1\n").unwrap();
+
+    // once the first reading is in, the source is waiting on events
+    let rx = follow(&path, WatchMode::CloseWrite);
+    assert_eq!(next(&rx), 1.0);
+
+    // a slow writer: "4", a pause, then "2"
+    let mut file = fs::File::create(&path).unwrap();
+    file.write_all(b"4").unwrap();
+    file.flush().unwrap();
+    thread::sleep(Duration::from_millis(300));
+    file.write_all(b"2\n").unwrap();
+    drop(file);
+
+    assert_eq!(next(&rx), 42.0);
+}
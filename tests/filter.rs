@@ -0,0 +1,119 @@
+// Each filter on made up noisy speeds, sampled ten times a second
+
+use std::time::{Duration, Instant};
+
+use speedometer::filter::{Ema, Filter, FilterChain, FilterConfig, Kalman, Median, MovingAverage, RateLimit};
+
+const PERIOD: Duration = Duration::from_millis(100);
+
+// Noise from -amplitude to amplitude, the same every run
+fn noise(n: usize, amplitude: f32) -> Vec<f32> {
+    let mut state = 12345u32;
+    (0..n)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((state >> 16) as f32 / 32767.5 - 1.0) * amplitude
+        })
+        .collect()
+}
+
+// A steady 50 with noise on top
+fn steady(n: usize, amplitude: f32) -> Vec<f32> {
+    noise(n, amplitude).into_iter().map(|n| 50.0 + n).collect()
+}
+
+fn run(filter: &mut dyn Filter, samples: &[f32]) -> Vec<f32> {
+    let t = Instant::now();
+    samples.iter().enumerate().map(|(i, &v)| filter.apply(v, t + PERIOD * i as u32)).collect()
+}
+
+// Largest distance from 50 after the first `skip` outputs, once the filter has warmed up
+fn worst(out: &[f32], skip: usize) -> f32 {
+    out[skip..].iter().map(|v| (v - 50.0).abs()).fold(0.0, f32::max)
+}
+
+// Root mean square distance from 50, likewise
+fn rms(out: &[f32], skip: usize) -> f32 {
+    let out = &out[skip..];
+    (out.iter().map(|v| (v - 50.0).powi(2)).sum::<f32>() / out.len() as f32).sqrt()
+}
+
+#[test]
+fn moving_average_smooths_noise() {
+    let samples = steady(200, 4.0);
+    let out = run(&mut MovingAverage::new(10), &samples);
+    // uniform noise of ±4 is about 2.3 rms, averaging 10 takes it to about a third
+    assert!(rms(&samples, 0) > 2.0);
+    assert!(rms(&out, 10) < 1.0, "{}", rms(&out, 10));
+    // a constant goes straight through
+    assert_eq!(run(&mut MovingAverage::new(4), &[7.0; 6]), vec![7.0; 6]);
+}
+
+#[test]
+fn ema_smooths_noise_and_follows_a_step() {
+    let samples = steady(200, 4.0);
+    let out = run(&mut Ema::new(0.2), &samples);
+    assert!(rms(&out, 20) < 1.0, "{}", rms(&out, 20));
+
+    let out = run(&mut Ema::new(0.5), &[0.0, 10.0, 10.0, 10.0]);
+    assert_eq!(out, vec![0.0, 5.0, 7.5, 8.75]);
+}
+
+#[test]
+fn median_drops_spikes() {
+    let mut samples = steady(100, 0.5);
+    // a GPS jump now and then
+    for i in (10..100).step_by(17) {
+        samples[i] = 250.0;
+    }
+    let out = run(&mut Median::new(5), &samples);
+    assert!(worst(&out, 0) < 0.5, "{}", worst(&out, 0));
+
+    // a real change gets through after half the window
+    let out = run(&mut Median::new(3), &[10.0, 10.0, 30.0, 30.0, 30.0]);
+    assert_eq!(out, vec![10.0, 10.0, 10.0, 30.0, 30.0]);
+}
+
+#[test]
+fn rate_limit_caps_the_change() {
+    // 20 units a second is 2 a sample
+    let out = run(&mut RateLimit::new(20.0), &[0.0, 100.0, 100.0, 100.0, 1.0, 1.0]);
+    assert_eq!(out, vec![0.0, 2.0, 4.0, 6.0, 4.0, 2.0]);
+
+    let samples = steady(200, 4.0);
+    let out = run(&mut RateLimit::new(5.0), &samples);
+    assert!(out.windows(2).all(|w| (w[1] - w[0]).abs() <= 0.5 + 1e-4));
+}
+
+#[test]
+fn kalman_smooths_noise_and_catches_up() {
+    let samples = steady(200, 4.0);
+    let out = run(&mut Kalman::new(1.0, 16.0), &samples);
+    assert!(rms(&out, 20) < 1.0, "{}", rms(&out, 20));
+
+    // the speed really changes, the filter follows it
+    let mut ramp: Vec<f32> = (0..100).map(|i| i as f32).collect();
+    for (v, n) in ramp.iter_mut().zip(noise(100, 2.0)) {
+        *v += n;
+    }
+    let out = run(&mut Kalman::new(50.0, 4.0), &ramp);
+    assert!((out[99] - 99.0).abs() < 5.0, "{}", out[99]);
+}
+
+#[test]
+fn a_chain_applies_in_order() {
+    let mut samples = steady(100, 2.0);
+    samples[50] = 250.0;
+    let mut chain = FilterChain::new(&[FilterConfig::Median { window: 5 }, FilterConfig::Ema { alpha: 0.3 }]);
+    let out = run_chain(&mut chain, &samples);
+    assert!(worst(&out, 10) < 2.0, "{}", worst(&out, 10));
+    assert!(rms(&out, 10) < 1.0, "{}", rms(&out, 10));
+
+    // nothing configured passes straight through
+    assert_eq!(run_chain(&mut FilterChain::default(), &samples), samples);
+}
+
+fn run_chain(chain: &mut FilterChain, samples: &[f32]) -> Vec<f32> {
+    let t = Instant::now();
+    samples.iter().enumerate().map(|(i, &v)| chain.apply(v, t + PERIOD * i as u32)).collect()
+}
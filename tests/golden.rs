@@ -0,0 +1,171 @@
+// Renders the gauge at a fixed set of speeds and compares each frame with the
+// reference in tests/golden/. Run with UPDATE_GOLDEN=1 to rewrite the references
+// after an intended layout change; on a mismatch the actual frame and a diff are
+// written to target/golden/ for a look.
+
+use std::{env, fs, path::PathBuf};
+
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+use speedometer::{gauge, Dial, Framebuffer, GaugeSpec, Theme};
+
+fn check(name: &str, speed: f32) {
+    check_spec(name, &GaugeSpec::default(), speed);
+}
+
+fn check_spec(name: &str, spec: &GaugeSpec, speed: f32) {
+    let mut display = Framebuffer::new();
+    gauge::render(&mut display, spec, speed).unwrap();
+    compare(&format!("speed_{}", name), &display);
+}
+
+fn compare(name: &str, display: &Framebuffer) {
+    let actual = display.to_raw();
+
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let reference = manifest.join("tests/golden").join(format!("{}.raw", name));
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&reference, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read(&reference).unwrap_or_else(|e| {
+        panic!("missing reference {}: {} (run with UPDATE_GOLDEN=1 to create it)", reference.display(), e)
+    });
+    if expected == actual {
+        return;
+    }
+
+    // highlight changed pixels in red over a dimmed copy of the new frame
+    let expected = Framebuffer::from_raw(&expected).unwrap();
+    let mut changed = 0;
+    let mut diff = Framebuffer::new();
+    for (i, (a, e)) in display.pixels().iter().zip(expected.pixels()).enumerate() {
+        let color = if a == e {
+            Rgb565::new(a.r() / 4, a.g() / 4, a.b() / 4)
+        } else {
+            changed += 1;
+            Rgb565::RED
+        };
+        let point = Point::new(i as i32 % 240, i as i32 / 240);
+        Pixel(point, color).draw(&mut diff).unwrap();
+    }
+
+    let out_dir = manifest.join("target/golden");
+    fs::create_dir_all(&out_dir).unwrap();
+    let actual_path = out_dir.join(format!("{}.actual.ppm", name));
+    let diff_path = out_dir.join(format!("{}.diff.ppm", name));
+    display.write_ppm(&actual_path).unwrap();
+    diff.write_ppm(&diff_path).unwrap();
+
+    panic!(
+        "{} differs from {} in {} pixels, see {} and {}",
+        name,
+        reference.display(),
+        changed,
+        actual_path.display(),
+        diff_path.display()
+    );
+}
+
+#[test]
+fn speed_0() {
+    check("0", 0.0);
+}
+
+#[test]
+fn speed_0_5() {
+    check("0_5", 0.5);
+}
+
+#[test]
+fn speed_37() {
+    check("37", 37.0);
+}
+
+#[test]
+fn speed_60() {
+    check("60", 60.0);
+}
+
+#[test]
+fn speed_120() {
+    check("120", 120.0);
+}
+
+#[test]
+fn speed_below_range() {
+    check("neg_10", -10.0);
+}
+
+#[test]
+fn speed_above_range() {
+    check("150", 150.0);
+}
+
+#[test]
+fn kmh_270_degree_sweep() {
+    let spec = GaugeSpec {
+        max: 200.0,
+        start_angle: 135.0,
+        end_angle: 405.0,
+        major_tick: 20.0,
+        minor_tick: 10.0,
+        label_step: 40.0,
+        unit: "km/h".to_string(),
+        ..GaugeSpec::default()
+    };
+    check_spec("kmh_88", &spec, 88.0);
+}
+
+#[test]
+fn tachometer() {
+    let spec = GaugeSpec {
+        max: 8.0,
+        start_angle: 150.0,
+        end_angle: 390.0,
+        major_tick: 1.0,
+        minor_tick: 0.5,
+        label_step: 1.0,
+        unit: "x1000".to_string(),
+        ..GaugeSpec::default()
+    };
+    check_spec("rpm_3_5", &spec, 3.5);
+}
+
+// a range that is not a whole number of minor ticks, the last tick stays inside it
+#[test]
+fn uneven_range() {
+    let spec = GaugeSpec { max: 125.0, ..GaugeSpec::default() };
+    assert_eq!(spec.ticks().last().map(|t| t.value), Some(120.0));
+    let rpm = GaugeSpec { max: 8000.0, minor_tick: 300.0, major_tick: 600.0, label_step: 1200.0, ..GaugeSpec::default() };
+    assert_eq!(rpm.ticks().last().map(|t| t.value), Some(7800.0));
+    // and one that is keeps its tick on max
+    assert_eq!(GaugeSpec::default().ticks().last().map(|t| t.value), Some(GaugeSpec::default().max));
+    check_spec("uneven_125_60", &spec, 60.0);
+}
+
+// no signal: greyed needle where it was, dashes and the warning sign
+#[test]
+fn signal_lost() {
+    let (spec, theme) = (GaugeSpec::default(), Theme::default());
+    let mut display = Framebuffer::new();
+    gauge::draw_dial(&mut display, &spec, &theme).unwrap();
+    gauge::draw_signal_lost(&mut display, &spec, &theme, 37.0).unwrap();
+    compare("signal_lost_37", &display);
+}
+
+// the cached dial has to produce exactly the frame a full redraw does
+#[test]
+fn cached_dial_matches_full_render() {
+    let dial = Dial::default();
+    for speed in [0.0, 37.0, 120.0] {
+        let mut full = Framebuffer::new();
+        gauge::render(&mut full, &GaugeSpec::default(), speed).unwrap();
+
+        let mut cached = Framebuffer::new();
+        dial.draw(&mut cached, speed).unwrap();
+
+        assert!(full.pixels() == cached.pixels(), "cached dial differs at speed {}", speed);
+    }
+}
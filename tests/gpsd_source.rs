@@ -0,0 +1,141 @@
+// A fake gpsd on loopback, just enough of the protocol to feed `GpsdSource`
+
+mod common;
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::Receiver,
+    thread,
+    time::{Duration, Instant},
+};
+
+use speedometer::{
+    source::{GpsdConfig, GpsdSource},
+    Speed, SpeedSource, SpeedUnit,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+const VERSION: &str = r#"{"class":"VERSION","release":"3.22","rev":"3.22","proto_major":3,"proto_minor":14}"#;
+
+fn tpv(mode: u8, speed: f32) -> String {
+    format!(r#"{{"class":"TPV","device":"/dev/ttyACM0","mode":{},"speed":{},"track":271.5}}"#, mode, speed)
+}
+
+// Runs a source against the fake on a thread, readings come out of the receiver
+fn start(listener: &TcpListener) -> Receiver<Result<Speed, String>> {
+    let config = GpsdConfig { address: listener.local_addr().unwrap().to_string() };
+    common::readings(GpsdSource::new(&config))
+}
+
+// Takes the next client and checks it asked for JSON reports
+fn accept(listener: &TcpListener) -> TcpStream {
+    let (stream, _) = listener.accept().unwrap();
+    let mut watch = String::new();
+    BufReader::new(&stream).read_line(&mut watch).unwrap();
+    assert_eq!(watch, "?WATCH={\"enable\":true,\"json\":true}\n");
+    stream
+}
+
+fn send(mut stream: &TcpStream, reports: &[&str]) {
+    for report in reports {
+        writeln!(stream, "{}", report).unwrap();
+    }
+}
+
+fn next(rx: &Receiver<Result<Speed, String>>) -> Result<Speed, String> {
+    rx.recv_timeout(TIMEOUT).expect("no reading")
+}
+
+#[test]
+fn reads_speed_from_tpv_reports() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let rx = start(&listener);
+    let gpsd = accept(&listener);
+
+    let sky = r#"{"class":"SKY","device":"/dev/ttyACM0","satellites":[]}"#;
+    send(&gpsd, &[VERSION, sky, &tpv(3, 12.5), "not json", &tpv(2, 13.0)]);
+    assert_eq!(next(&rx), Ok(Speed::new(12.5, SpeedUnit::MetersPerSecond)));
+    assert_eq!(next(&rx), Ok(Speed::new(13.0, SpeedUnit::MetersPerSecond)));
+}
+
+#[test]
+fn reports_losing_the_fix_once() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let rx = start(&listener);
+    let gpsd = accept(&listener);
+
+    send(&gpsd, &[&tpv(3, 5.0), &tpv(1, 0.0), &tpv(1, 0.0), r#"{"class":"TPV","mode":0}"#, &tpv(3, 6.0)]);
+    assert_eq!(next(&rx), Ok(Speed::new(5.0, SpeedUnit::MetersPerSecond)));
+    assert_eq!(next(&rx), Err("no fix".to_string()));
+    assert_eq!(next(&rx), Ok(Speed::new(6.0, SpeedUnit::MetersPerSecond)));
+}
+
+#[test]
+fn reconnects_when_gpsd_goes_away() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let rx = start(&listener);
+
+    let gpsd = accept(&listener);
+    send(&gpsd, &[&tpv(3, 7.0)]);
+    assert_eq!(next(&rx), Ok(Speed::new(7.0, SpeedUnit::MetersPerSecond)));
+    drop(gpsd);
+    assert!(next(&rx).unwrap_err().contains("closed the connection"));
+
+    // gpsd restarted, the source comes back by itself
+    let gpsd = accept(&listener);
+    send(&gpsd, &[&tpv(3, 8.0)]);
+    assert_eq!(next(&rx), Ok(Speed::new(8.0, SpeedUnit::MetersPerSecond)));
+}
+
+#[test]
+fn backs_off_when_gpsd_keeps_hanging_up() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let rx = start(&listener);
+    let gpsd = accept(&listener);
+    send(&gpsd, &[&tpv(3, 7.0)]);
+    assert_eq!(next(&rx), Ok(Speed::new(7.0, SpeedUnit::MetersPerSecond)));
+
+    // takes every connection and drops it straight away
+    listener.set_nonblocking(true).unwrap();
+    drop(gpsd);
+    assert!(next(&rx).unwrap_err().contains("closed the connection"));
+    let mut hangups = 0;
+    let until = Instant::now() + Duration::from_millis(2500);
+    while Instant::now() < until {
+        match listener.accept() {
+            Ok(_) => hangups += 1,
+            Err(_) => thread::sleep(Duration::from_millis(10)),
+        }
+    }
+    // a try a second, and nothing said about it
+    assert!((1..=3).contains(&hangups), "{} connections", hangups);
+    assert!(rx.try_recv().is_err());
+
+    // until gpsd works again
+    listener.set_nonblocking(false).unwrap();
+    let gpsd = accept(&listener);
+    send(&gpsd, &[&tpv(3, 8.0)]);
+    assert_eq!(next(&rx), Ok(Speed::new(8.0, SpeedUnit::MetersPerSecond)));
+}
+
+#[test]
+fn waits_for_gpsd_to_start() {
+    // a port nothing listens on, until it does
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    drop(listener);
+
+    let mut source = GpsdSource::new(&GpsdConfig { address: address.to_string() });
+    let err = source.next_speed().unwrap_err();
+    assert!(format!("{:#}", err).starts_with("Unable to connect to gpsd"), "{:#}", err);
+
+    let listener = TcpListener::bind(address).unwrap();
+    thread::spawn(move || {
+        let gpsd = accept(&listener);
+        send(&gpsd, &[&tpv(3, 9.0)]);
+        thread::sleep(TIMEOUT);
+    });
+    assert_eq!(source.next_speed().unwrap(), Speed::new(9.0, SpeedUnit::MetersPerSecond));
+}
@@ -0,0 +1,106 @@
+// Samples posted to the HTTP server and streamed over its WebSocket
+
+mod common;
+
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::mpsc::Receiver,
+    time::Duration,
+};
+
+use speedometer::{
+    source::{HttpConfig, HttpSource},
+    Speed, SpeedUnit,
+};
+use tungstenite::Message;
+
+fn readings() -> (SocketAddr, Receiver<Result<Speed, String>>) {
+    let source = HttpSource::bind(&HttpConfig { bind: "127.0.0.1:0".to_string() }).unwrap();
+    let addr = source.local_addr().unwrap();
+    (addr, common::readings(source))
+}
+
+fn next(rx: &Receiver<Result<Speed, String>>) -> Result<Speed, String> {
+    rx.recv_timeout(Duration::from_secs(5)).expect("no reading")
+}
+
+// The status code and body of the reply
+fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        addr,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).unwrap();
+
+    let status = reply.split(' ').nth(1).unwrap().parse().unwrap();
+    let body = reply.split_once("\r\n\r\n").map(|(_, body)| body.to_string()).unwrap_or_default();
+    (status, body)
+}
+
+#[test]
+fn post_speed() {
+    let (addr, rx) = readings();
+
+    assert_eq!(request(addr, "POST", "/speed", "42").0, 204);
+    assert_eq!(next(&rx), Ok(Speed::bare(42.0)));
+    assert_eq!(request(addr, "POST", "/speed?from=phone", "67.6 km/h\n").0, 204);
+    assert_eq!(next(&rx), Ok(Speed::new(67.6, SpeedUnit::Kmh)));
+    let telemetry = r#"{"speed": 18.5, "unit": "m/s", "rpm": 2400}"#;
+    assert_eq!(request(addr, "POST", "/speed", telemetry).0, 204);
+    assert_eq!(next(&rx), Ok(Speed::new(18.5, SpeedUnit::MetersPerSecond)));
+
+    // a bad sample is turned away and shows up as an error
+    let (status, body) = request(addr, "POST", "/speed", "fast");
+    assert_eq!(status, 400);
+    assert!(body.contains("not a number"), "{}", body);
+    assert!(next(&rx).unwrap_err().starts_with("Bad sample over HTTP"));
+    assert_eq!(request(addr, "POST", "/speed", &"1".repeat(2000)).0, 400);
+    assert!(next(&rx).is_err());
+}
+
+#[test]
+fn other_requests() {
+    let (addr, rx) = readings();
+    assert_eq!(request(addr, "GET", "/speed", "").0, 405);
+    assert_eq!(request(addr, "POST", "/", "42").0, 404);
+    // not a WebSocket handshake
+    assert_eq!(request(addr, "GET", "/ws", "").0, 400);
+    // and none of them were samples
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+}
+
+#[test]
+fn websocket_stream() {
+    let (addr, rx) = readings();
+    let (mut socket, _) = tungstenite::connect(format!("ws://{}/ws", addr)).unwrap();
+
+    for i in 0..200 {
+        socket.send(Message::Text(format!("{}", i as f32 / 2.0))).unwrap();
+    }
+    for i in 0..200 {
+        assert_eq!(next(&rx), Ok(Speed::bare(i as f32 / 2.0)));
+    }
+
+    socket.send(Message::Text(r#"{"speed": 30, "unit": "mph"}"#.to_string())).unwrap();
+    assert_eq!(next(&rx), Ok(Speed::new(30.0, SpeedUnit::Mph)));
+    socket.send(Message::Text("nope".to_string())).unwrap();
+    assert!(next(&rx).unwrap_err().starts_with("Bad sample over WebSocket"));
+    socket.send(Message::Binary(vec![42])).unwrap();
+    assert!(next(&rx).is_err());
+
+    // posting still works next to an open socket
+    assert_eq!(request(addr, "POST", "/speed", "7").0, 204);
+    assert_eq!(next(&rx), Ok(Speed::bare(7.0)));
+
+    socket.close(None).unwrap();
+    socket.flush().ok();
+}
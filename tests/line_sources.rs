@@ -0,0 +1,101 @@
+// Samples a line at a time, from a stream, over a Unix socket, and piped into
+// the speedometer itself
+
+mod common;
+
+use std::{
+    env, fs,
+    io::{Cursor, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use speedometer::{
+    source::{lines::parse_line, Ended, LineSource, UnixConfig, UnixSource},
+    Speed, SpeedSource, SpeedUnit,
+};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("speedometer-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn text_and_json_lines() {
+    assert_eq!(parse_line("42\n").unwrap(), Speed::bare(42.0));
+    assert_eq!(parse_line("67.6 km/h").unwrap(), Speed::new(67.6, SpeedUnit::Kmh));
+    assert_eq!(parse_line(r#"{"speed": 67.6, "unit": "km/h"}"#).unwrap(), Speed::new(67.6, SpeedUnit::Kmh));
+    assert_eq!(parse_line(r#"{"speed": 12, "rpm": 3000, "t": 1.5}"#).unwrap(), Speed::bare(12.0));
+
+    for line in [r#"{"speed": -1}"#, r#"{"speed": "fast"}"#, r#"{"speed": 1, "unit": "furlongs"}"#, "{", "abc"] {
+        assert!(parse_line(line).is_err(), "{}", line);
+    }
+}
+
+#[test]
+fn a_sample_per_line_until_the_end() {
+    let mut source = LineSource::new(Cursor::new("10\n\n{\"speed\": 20}\nnope\n30"));
+    assert_eq!(source.next_speed().unwrap().value, 10.0);
+    assert_eq!(source.next_speed().unwrap().value, 20.0);
+    assert!(source.next_speed().is_err());
+    // the last line does not need a newline
+    assert_eq!(source.next_speed().unwrap().value, 30.0);
+    assert!(source.next_speed().unwrap_err().is::<Ended>());
+}
+
+#[test]
+fn unix_socket_clients() {
+    let path = temp_dir("unix").join("speed.sock");
+    // a socket left over from before is taken over
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+    let rx = common::readings(UnixSource::bind(&UnixConfig { path: path.clone() }).unwrap());
+    let next = || rx.recv_timeout(Duration::from_secs(5)).expect("no reading");
+
+    let mut first = UnixStream::connect(&path).unwrap();
+    let mut second = UnixStream::connect(&path).unwrap();
+    first.write_all(b"42\n").unwrap();
+    assert_eq!(next(), Ok(Speed::bare(42.0)));
+    second.write_all(b"{\"speed\": 18.5, \"unit\": \"m/s\"}\n").unwrap();
+    assert_eq!(next(), Ok(Speed::new(18.5, SpeedUnit::MetersPerSecond)));
+
+    // a bad line is an error, and the client stays connected
+    first.write_all(b"garbage\n").unwrap();
+    assert!(next().unwrap_err().starts_with("Bad sample from client 1"));
+    first.write_all(b"43\n").unwrap();
+    assert_eq!(next(), Ok(Speed::bare(43.0)));
+}
+
+// Piping samples in gives the same picture as rendering the last one
+#[test]
+fn stdin_drives_the_simulator() {
+    let dir = temp_dir("stdin");
+    let speedometer = env!("CARGO_BIN_EXE_speedometer");
+    let common = ["--config", "speedometer.toml", "--backend", "simulator", "-q"];
+
+    let mut child = Command::new(speedometer)
+        .args(common)
+        .arg("--stdin")
+        .arg("--out-dir")
+        .arg(&dir)
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"10\n25 mph\n{\"speed\": 42}\n").unwrap();
+    assert!(child.wait().unwrap().success());
+
+    let rendered = dir.join("rendered.ppm");
+    let status = Command::new(speedometer)
+        .args(common)
+        .args(["render", "--speed", "42", "--out"])
+        .arg(&rendered)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(fs::read(dir.join("speedometer.ppm")).unwrap(), fs::read(rendered).unwrap());
+}
@@ -0,0 +1,219 @@
+// The MQTT source against a stand-in broker that speaks just enough MQTT 3.1.1
+
+mod common;
+
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::Receiver,
+    time::Duration,
+};
+
+use speedometer::{
+    source::{mqtt::parse_payload, MqttConfig, MqttSource},
+    Speed, SpeedUnit,
+};
+
+struct Broker {
+    listener: TcpListener,
+}
+
+// One client connection, after CONNECT and SUBSCRIBE
+struct Session {
+    stream: TcpStream,
+    topic: String,
+    qos: u8,
+}
+
+impl Broker {
+    fn new() -> Self {
+        Self { listener: TcpListener::bind("127.0.0.1:0").unwrap() }
+    }
+
+    fn address(&self) -> String {
+        self.listener.local_addr().unwrap().to_string()
+    }
+
+    // Takes the next client through CONNECT/CONNACK and SUBSCRIBE/SUBACK
+    fn accept(&self) -> Session {
+        let (mut stream, _) = self.listener.accept().unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let (kind, _) = read_packet(&mut stream);
+        assert_eq!(kind, 0x10, "expected CONNECT");
+        stream.write_all(&[0x20, 2, 0, 0]).unwrap();
+
+        let (kind, body) = read_packet(&mut stream);
+        assert_eq!(kind, 0x82, "expected SUBSCRIBE");
+        let len = u16::from_be_bytes([body[2], body[3]]) as usize;
+        let topic = String::from_utf8(body[4..4 + len].to_vec()).unwrap();
+        let qos = body[4 + len];
+        stream.write_all(&[0x90, 3, body[0], body[1], qos]).unwrap();
+
+        Session { stream, topic, qos }
+    }
+}
+
+impl Session {
+    fn publish(&mut self, topic: &str, payload: &str, packet_id: Option<u16>) {
+        let mut body = (topic.len() as u16).to_be_bytes().to_vec();
+        body.extend_from_slice(topic.as_bytes());
+        if let Some(id) = packet_id {
+            body.extend_from_slice(&id.to_be_bytes());
+        }
+        body.extend_from_slice(payload.as_bytes());
+        let kind = if packet_id.is_some() { 0x32 } else { 0x30 };
+        write_packet(&mut self.stream, kind, &body);
+    }
+
+    // The next packet that is not a PINGREQ
+    fn read(&mut self) -> (u8, Vec<u8>) {
+        loop {
+            match read_packet(&mut self.stream) {
+                (0xC0, _) => self.stream.write_all(&[0xD0, 0]).unwrap(),
+                packet => return packet,
+            }
+        }
+    }
+}
+
+fn read_packet(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let mut byte = [0];
+    stream.read_exact(&mut byte).unwrap();
+    let kind = byte[0];
+    // remaining length, 7 bits a byte
+    let (mut len, mut shift) = (0, 0);
+    loop {
+        stream.read_exact(&mut byte).unwrap();
+        len |= ((byte[0] & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+    let mut body = vec![0; len];
+    stream.read_exact(&mut body).unwrap();
+    (kind, body)
+}
+
+fn write_packet(stream: &mut TcpStream, kind: u8, body: &[u8]) {
+    let mut packet = vec![kind];
+    let mut len = body.len();
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        packet.push(if len > 0 { byte | 0x80 } else { byte });
+        if len == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    stream.write_all(&packet).unwrap();
+}
+
+// Readings from the source, run on its own thread as the speedometer does
+fn readings(config: MqttConfig) -> Receiver<Result<Speed, String>> {
+    common::readings(MqttSource::new(&config).unwrap())
+}
+
+fn next(rx: &Receiver<Result<Speed, String>>) -> Result<Speed, String> {
+    rx.recv_timeout(Duration::from_secs(10)).expect("no reading")
+}
+
+fn config(broker: &Broker) -> MqttConfig {
+    MqttConfig { broker: broker.address(), reconnect_min_ms: 50, reconnect_max_ms: 200, ..MqttConfig::default() }
+}
+
+#[test]
+fn payloads() {
+    let kmh = Some(SpeedUnit::Kmh);
+    assert_eq!(parse_payload(b"42", "speed", None).unwrap(), Speed::bare(42.0));
+    assert_eq!(parse_payload(b" 42\n", "speed", kmh).unwrap(), Speed::new(42.0, SpeedUnit::Kmh));
+    assert_eq!(parse_payload(b"18 m/s", "speed", kmh).unwrap(), Speed::new(18.0, SpeedUnit::MetersPerSecond));
+    assert_eq!(parse_payload(br#"{"speed": 12.5, "rpm": 2000}"#, "speed", None).unwrap(), Speed::bare(12.5));
+    assert_eq!(
+        parse_payload(br#"{"telemetry": {"speed": 30, "heading": 90}}"#, "telemetry.speed", kmh).unwrap(),
+        Speed::new(30.0, SpeedUnit::Kmh)
+    );
+    assert_eq!(
+        parse_payload(br#"{"v": {"speed": "20 mph"}}"#, "v.speed", kmh).unwrap(),
+        Speed::new(20.0, SpeedUnit::Mph)
+    );
+
+    for payload in [&b"fast"[..], b"-3", b"{\"speed\": -1}", b"{\"speed\": null}", b"{\"other\": 1}", b"{", b"\xff"] {
+        assert!(parse_payload(payload, "speed", None).is_err(), "{:?}", payload);
+    }
+    assert!(parse_payload(br#"{"telemetry": 5}"#, "telemetry.speed", None).is_err());
+}
+
+#[test]
+fn validates_the_config() {
+    assert!(MqttConfig::default().validate().is_ok());
+    for config in [
+        MqttConfig { broker: "localhost".to_string(), ..MqttConfig::default() },
+        MqttConfig { broker: ":1883".to_string(), ..MqttConfig::default() },
+        MqttConfig { qos: 3, ..MqttConfig::default() },
+        MqttConfig { field: "a..b".to_string(), ..MqttConfig::default() },
+        MqttConfig { unit: Some("furlongs".to_string()), ..MqttConfig::default() },
+        MqttConfig { reconnect_min_ms: 100, reconnect_max_ms: 50, ..MqttConfig::default() },
+    ] {
+        assert!(config.validate().is_err(), "{:?}", config);
+    }
+}
+
+#[test]
+fn subscribes_and_reads_the_topic() {
+    let broker = Broker::new();
+    let config = MqttConfig {
+        topic: "car/telemetry".to_string(),
+        qos: 1,
+        field: "data.speed".to_string(),
+        unit: Some("km/h".to_string()),
+        ..config(&broker)
+    };
+    let rx = readings(config);
+
+    let mut session = broker.accept();
+    assert_eq!((session.topic.as_str(), session.qos), ("car/telemetry", 1));
+
+    session.publish("car/telemetry", "55", None);
+    assert_eq!(next(&rx), Ok(Speed::new(55.0, SpeedUnit::Kmh)));
+    session.publish("car/telemetry", r#"{"data": {"speed": 61.5}}"#, Some(7));
+    assert_eq!(next(&rx), Ok(Speed::new(61.5, SpeedUnit::Kmh)));
+    // at least once, so the broker gets its PUBACK
+    assert_eq!(session.read(), (0x40, vec![0, 7]));
+
+    session.publish("car/telemetry", r#"{"data": {}}"#, None);
+    assert!(next(&rx).unwrap_err().starts_with("Bad speed on car/telemetry"));
+}
+
+#[test]
+fn reconnects_after_losing_the_broker() {
+    let broker = Broker::new();
+    let rx = readings(config(&broker));
+
+    let mut session = broker.accept();
+    session.publish("vehicle/speed", "40", None);
+    assert_eq!(next(&rx), Ok(Speed::bare(40.0)));
+
+    drop(session);
+    assert!(next(&rx).unwrap_err().starts_with("MQTT disconnected"));
+
+    // back, subscribed again and reading
+    let mut session = broker.accept();
+    assert_eq!(session.topic, "vehicle/speed");
+    session.publish("vehicle/speed", "41", None);
+    assert_eq!(next(&rx), Ok(Speed::bare(41.0)));
+}
+
+#[test]
+fn reports_a_missing_broker_once() {
+    let broker = Broker::new();
+    let config = config(&broker);
+    drop(broker);
+
+    let rx = readings(config);
+    assert!(next(&rx).unwrap_err().starts_with("MQTT disconnected"));
+    // then it keeps trying quietly
+    assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+}
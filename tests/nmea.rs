@@ -0,0 +1,74 @@
+// NMEA sentences on their own, and a recorded drive replayed through `NmeaSource`
+
+use std::{
+    fs::File,
+    io::{BufReader, Cursor},
+};
+
+use speedometer::{
+    nmea::{self, Sentence},
+    source::NmeaSource,
+    Speed, SpeedSource, SpeedUnit,
+};
+
+#[test]
+fn parses_rmc_vtg_and_gga() {
+    let rmc = nmea::parse("$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A\r\n").unwrap();
+    assert_eq!(rmc, Some(Sentence::Rmc { valid: true, speed_knots: Some(22.4), course: Some(84.4) }));
+
+    let vtg = nmea::parse(&nmea::sentence("GNVTG,054.7,T,034.4,M,005.5,N,010.2,K,A")).unwrap();
+    let want = Sentence::Vtg { speed_knots: Some(5.5), speed_kmh: Some(10.2), course: Some(54.7), valid: true };
+    assert_eq!(vtg, Some(want));
+
+    let gga = nmea::parse(&nmea::sentence("GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,")).unwrap();
+    assert_eq!(gga, Some(Sentence::Gga { quality: 1, satellites: Some(8) }));
+
+    // other sentences are fine, just of no interest
+    assert_eq!(nmea::parse(&nmea::sentence("GPGSA,A,3,04,05,,09,12,,,24,,,,,2.5,1.3,2.1")).unwrap(), None);
+    // nor is line noise in the talker or type, e.g. a byte lost to from_utf8_lossy
+    assert_eq!(nmea::parse(&nmea::sentence("G\u{FFFD}C,1")).unwrap(), None);
+    assert_eq!(nmea::parse(&nmea::sentence("GPR\u{e9}C,1")).unwrap(), None);
+}
+
+#[test]
+fn rejects_bad_checksums() {
+    for line in [
+        "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6B",
+        "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W",
+        "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*ZZ",
+        "GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A",
+    ] {
+        assert!(nmea::parse(line).is_err(), "{}", line);
+    }
+}
+
+#[test]
+fn replays_a_recorded_drive() {
+    let log = File::open("tests/data/drive.nmea").unwrap();
+    let mut source = NmeaSource::new(BufReader::new(log), "drive.nmea");
+    let mut next = || source.next_speed().map(|s| s.value).map_err(|e| e.to_string());
+
+    // no fix to start with
+    assert_eq!(next(), Err("no fix".to_string()));
+    // RMC and VTG both carry the speed
+    assert_eq!(next(), Ok(10.0));
+    assert_eq!(next(), Ok(10.0));
+    // the line with the bad checksum is skipped
+    assert_eq!(next(), Ok(30.0));
+    // lost, once, and back
+    assert_eq!(next(), Err("no fix".to_string()));
+    assert_eq!(next(), Ok(40.0));
+    assert_eq!(next(), Err("NMEA input from drive.nmea ended".to_string()));
+
+    let fix = source.fix();
+    assert_eq!((fix.valid, fix.quality, fix.satellites, fix.heading), (Some(true), 2, Some(9), Some(92.0)));
+}
+
+#[test]
+fn speed_is_in_knots() {
+    let line = nmea::sentence("GPRMC,000000,A,0000.000,N,00000.000,E,010.0,000.0,010100,,,A") + "\r\n";
+    let mut source = NmeaSource::new(Cursor::new(line), "stdin");
+    let speed = source.next_speed().unwrap();
+    assert_eq!(speed, Speed::new(10.0, SpeedUnit::Knots));
+    assert!((speed.in_unit(Some(SpeedUnit::Kmh)).unwrap() - 18.52).abs() < 0.001);
+}
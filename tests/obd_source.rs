@@ -0,0 +1,146 @@
+// An emulated ELM327 adapter, on a pty like a USB adapter and on TCP like a
+// WiFi one, with `ObdSource` polling it
+
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    net::TcpListener,
+    thread,
+    time::Duration,
+};
+
+use serialport::{SerialPort, TTYPort};
+use speedometer::{
+    obd::{self, Pid},
+    source::{ObdConfig, ObdSource},
+    Speed, SpeedSource, SpeedUnit,
+};
+
+// Answers like an ELM327 with a car behind it doing `speeds`, one per request,
+// then NO DATA. Runs until the other end goes away.
+fn emulate(mut io: impl Read + Write, speeds: Vec<Option<u8>>) {
+    let mut speeds = VecDeque::from(speeds);
+    let mut echo = true;
+    let mut searching = true;
+    let mut command = Vec::new();
+    let mut buf = [0; 64];
+    loop {
+        let n = match io.read(&mut buf) {
+            Ok(0) => return,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => continue,
+            Err(_) => return,
+        };
+        for &b in &buf[..n] {
+            if b != b'\r' {
+                command.push(b);
+                continue;
+            }
+            let text = String::from_utf8_lossy(&command).into_owned();
+            command.clear();
+
+            let reply = match text.as_str() {
+                "ATZ" => {
+                    echo = true;
+                    "\r\rELM327 v1.5".to_string()
+                },
+                "ATE0" => {
+                    echo = false;
+                    "OK".to_string()
+                },
+                "ATSP0" => "OK".to_string(),
+                "010D" => match speeds.pop_front().flatten() {
+                    Some(kmh) if searching => {
+                        searching = false;
+                        format!("SEARCHING...\r41 0D {:02X}", kmh)
+                    },
+                    Some(kmh) => format!("41 0D {:02X}", kmh),
+                    None => "NO DATA".to_string(),
+                },
+                // 1726 rpm, without spaces like after ATS0
+                "010C" => "410C1AF8".to_string(),
+                "0105" => "41 05 5A".to_string(),
+                _ => "?".to_string(),
+            };
+            let echoed = if echo { format!("{}\r", text) } else { String::new() };
+            if io.write_all(format!("{}{}\r\r>", echoed, reply).as_bytes()).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn config() -> ObdConfig {
+    ObdConfig { poll_interval_ms: 10, ..ObdConfig::default() }
+}
+
+// A source on one end of a pty, the emulator on the other
+fn on_pty(speeds: Vec<Option<u8>>, config: &ObdConfig) -> ObdSource {
+    let (mut adapter, mut pi) = TTYPort::pair().unwrap();
+    adapter.set_timeout(Duration::from_millis(100)).unwrap();
+    pi.set_timeout(Duration::from_millis(100)).unwrap();
+    thread::spawn(move || emulate(adapter, speeds));
+    ObdSource::with_link(Box::new(pi), config)
+}
+
+fn kmh(value: f32) -> Speed {
+    Speed::new(value, SpeedUnit::Kmh)
+}
+
+#[test]
+fn parses_replies() {
+    assert_eq!(obd::parse_reply(Pid::Speed, "41 0D 32\r\r").unwrap(), Some(50.0));
+    assert_eq!(obd::parse_reply(Pid::Speed, "SEARCHING...\r410D32\r\r").unwrap(), Some(50.0));
+    assert_eq!(obd::parse_reply(Pid::Speed, "010D\r41 0D 00\r\r").unwrap(), Some(0.0));
+    assert_eq!(obd::parse_reply(Pid::Rpm, "41 0C 1A F8").unwrap(), Some(1726.0));
+    assert_eq!(obd::parse_reply(Pid::Coolant, "41 05 5A").unwrap(), Some(50.0));
+    // two ECUs answering, the one with the PID we asked for wins
+    assert_eq!(obd::parse_reply(Pid::Speed, "41 0C 1A F8\r41 0D 64\r").unwrap(), Some(100.0));
+
+    assert_eq!(obd::parse_reply(Pid::Speed, "SEARCHING...\rNO DATA\r\r").unwrap(), None);
+    for reply in ["?", "UNABLE TO CONNECT", "CAN ERROR", "BUS INIT: ...ERROR", "41 0D", "41 0D XY", ""] {
+        assert!(obd::parse_reply(Pid::Speed, reply).is_err(), "{:?}", reply);
+    }
+}
+
+#[test]
+fn polls_speed_over_a_serial_link() {
+    let mut source = on_pty(vec![Some(50), Some(51)], &ObdConfig { rpm: true, coolant: true, ..config() });
+    assert_eq!(source.next_speed().unwrap(), kmh(50.0));
+    assert_eq!(source.next_speed().unwrap(), kmh(51.0));
+
+    let readings = source.readings();
+    assert_eq!((readings.speed, readings.rpm, readings.coolant), (Some(51.0), Some(1726.0), Some(50.0)));
+}
+
+#[test]
+fn no_data_is_reported_once() {
+    let mut source = on_pty(vec![Some(10), None, None, None, Some(12)], &config());
+    assert_eq!(source.next_speed().unwrap(), kmh(10.0));
+    assert_eq!(source.next_speed().unwrap_err().to_string(), "no data from the car");
+    assert_eq!(source.next_speed().unwrap(), kmh(12.0));
+}
+
+#[test]
+fn polls_a_wifi_adapter() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let device = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        emulate(stream, vec![Some(88), Some(89)]);
+    });
+
+    let mut source = ObdSource::new(&ObdConfig { device, ..config() });
+    assert_eq!(source.next_speed().unwrap(), kmh(88.0));
+    assert_eq!(source.next_speed().unwrap(), kmh(89.0));
+}
+
+#[test]
+fn a_missing_adapter_is_an_error() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let device = listener.local_addr().unwrap().to_string();
+    drop(listener);
+
+    let mut source = ObdSource::new(&ObdConfig { device, ..config() });
+    assert!(source.next_speed().unwrap_err().to_string().starts_with("Unable to connect"));
+}
@@ -0,0 +1,239 @@
+// Drives a `Panel` over a fake bus that behaves like the GC9A01's frame memory,
+// so partial flushing can be checked for both correctness and SPI traffic
+// without the hardware.
+
+use std::{env, fs};
+
+use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+use speedometer::{
+    gauge::{self, Renderer},
+    Dial, DisplaySink, Framebuffer, GaugeSpec, Panel, Theme,
+};
+
+const FULL_FRAME_BYTES: usize = 240 * 240 * 2;
+
+// Interprets the address window and memory write commands and keeps count of
+// every byte that would go over the wire
+#[derive(Default)]
+struct MockBus {
+    gram: Vec<u8>,
+    command: u8,
+    params: Vec<u8>,
+    columns: (usize, usize),
+    rows: (usize, usize),
+    cursor: usize,
+    bytes_sent: usize,
+}
+
+impl MockBus {
+    fn new() -> Self {
+        Self { gram: vec![0; FULL_FRAME_BYTES], ..Default::default() }
+    }
+
+    fn bytes(buf: DataFormat<'_>) -> Vec<u8> {
+        match buf {
+            DataFormat::U8(bytes) => bytes.to_vec(),
+            _ => panic!("panel only sends bytes"),
+        }
+    }
+}
+
+impl WriteOnlyDataCommand for MockBus {
+    fn send_commands(&mut self, cmd: DataFormat<'_>) -> Result<(), DisplayError> {
+        let bytes = Self::bytes(cmd);
+        self.bytes_sent += bytes.len();
+        self.command = bytes[0];
+        self.params.clear();
+        self.cursor = 0;
+        Ok(())
+    }
+
+    fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        let bytes = Self::bytes(buf);
+        self.bytes_sent += bytes.len();
+        match self.command {
+            0x2A | 0x2B => {
+                self.params.extend_from_slice(&bytes);
+                if self.params.len() == 4 {
+                    let p = &self.params;
+                    let range = (u16::from_be_bytes([p[0], p[1]]) as usize, u16::from_be_bytes([p[2], p[3]]) as usize);
+                    if self.command == 0x2A {
+                        self.columns = range;
+                    } else {
+                        self.rows = range;
+                    }
+                }
+            },
+            0x2C => {
+                let width = self.columns.1 - self.columns.0 + 1;
+                for pixel in bytes.chunks_exact(2) {
+                    let x = self.columns.0 + self.cursor % width;
+                    let y = self.rows.0 + self.cursor / width;
+                    assert!(y <= self.rows.1, "wrote past the end of the window");
+                    let i = (y * 240 + x) * 2;
+                    self.gram[i..i + 2].copy_from_slice(pixel);
+                    self.cursor += 1;
+                }
+            },
+            other => panic!("unexpected command {:#04x}", other),
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn full_flush_sends_whole_frame() {
+    let mut panel = Panel::new(MockBus::new());
+    gauge::render(&mut panel, &GaugeSpec::default(), 42.0).unwrap();
+    panel.flush().unwrap();
+
+    assert_eq!(panel.frame().to_raw(), panel.bus().gram);
+}
+
+#[test]
+fn partial_flush_keeps_panel_in_sync() {
+    let mut panel = Panel::new(MockBus::new());
+    let mut renderer = Renderer::new(Dial::default());
+
+    // a sweep up, a jump back down and some fractions for the readout
+    let speeds = (0..=120).map(|s| s as f32).chain([3.0, 99.5, 0.5, 120.0, 7.25]);
+    for speed in speeds {
+        let regions = renderer.draw(&mut panel, speed).unwrap();
+        panel.flush_regions(&regions).unwrap();
+
+        let mut expected = Framebuffer::new();
+        gauge::render(&mut expected, &GaugeSpec::default(), speed).unwrap();
+        assert!(panel.bus().gram == expected.to_raw(), "panel out of sync at speed {}", speed);
+    }
+}
+
+#[test]
+fn partial_flush_sends_a_tenth_of_full_frames() {
+    let mut panel = Panel::new(MockBus::new());
+    let mut renderer = Renderer::new(Dial::default());
+
+    // first frame goes out in full
+    let regions = renderer.draw(&mut panel, 0.0).unwrap();
+    panel.flush_regions(&regions).unwrap();
+    let first = panel.bus().bytes_sent;
+    assert!(first >= FULL_FRAME_BYTES);
+
+    // then the same ramp update_speed.sh produces
+    let updates = 120;
+    for speed in 1..=updates {
+        let regions = renderer.draw(&mut panel, speed as f32).unwrap();
+        panel.flush_regions(&regions).unwrap();
+    }
+    let per_update = (panel.bus().bytes_sent - first) / updates;
+    assert!(
+        per_update * 10 <= FULL_FRAME_BYTES,
+        "{} bytes per update, full frame is {}",
+        per_update,
+        FULL_FRAME_BYTES
+    );
+}
+
+#[test]
+fn status_line_comes_and_goes_in_sync() {
+    let mut panel = Panel::new(MockBus::new());
+    let mut renderer = Renderer::new(Dial::default());
+    let spec = GaugeSpec::default();
+    let theme = Theme::default();
+
+    let steps = [
+        (10.0, None),
+        (10.0, Some("not a number: \"4x\"")),
+        (30.0, Some("over range: 150")),
+        (30.0, Some("a status far too long to fit on the dial")),
+        (55.0, None),
+    ];
+    for (speed, status) in steps {
+        renderer.set_status(status.map(String::from));
+        let regions = renderer.draw(&mut panel, speed).unwrap();
+        panel.flush_regions(&regions).unwrap();
+
+        let mut expected = Framebuffer::new();
+        gauge::render(&mut expected, &spec, speed).unwrap();
+        if let Some(status) = status {
+            gauge::draw_status(&mut expected, &spec, &theme, status).unwrap();
+        }
+        assert!(panel.bus().gram == expected.to_raw(), "panel out of sync with status {:?}", status);
+    }
+}
+
+#[test]
+fn badge_comes_and_goes_in_sync() {
+    let mut panel = Panel::new(MockBus::new());
+    let mut renderer = Renderer::new(Dial::default());
+    let spec = GaugeSpec::default();
+    let theme = Theme::default();
+
+    // the needle sweeps right through the badge at the top of the scale
+    let steps = [(10.0, None), (50.0, Some("OBD")), (60.0, Some("OBD")), (70.0, Some("GPSD")), (80.0, None)];
+    for (speed, badge) in steps {
+        renderer.set_badge(badge.map(String::from));
+        let regions = renderer.draw(&mut panel, speed).unwrap();
+        panel.flush_regions(&regions).unwrap();
+
+        let mut expected = Framebuffer::new();
+        gauge::render(&mut expected, &spec, speed).unwrap();
+        if let Some(badge) = badge {
+            gauge::draw_badge(&mut expected, &spec, &theme, badge).unwrap();
+        }
+        assert!(panel.bus().gram == expected.to_raw(), "panel out of sync with badge {:?}", badge);
+    }
+}
+
+#[test]
+fn signal_lost_comes_and_goes_in_sync() {
+    let mut panel = Panel::new(MockBus::new());
+    let mut renderer = Renderer::new(Dial::default());
+    let spec = GaugeSpec::default();
+    let theme = Theme::default();
+
+    let steps = [(40.0, false), (40.0, true), (40.0, true), (65.0, false), (65.0, true), (5.0, false)];
+    for (speed, lost) in steps {
+        renderer.set_signal_lost(lost);
+        let regions = renderer.draw(&mut panel, speed).unwrap();
+        panel.flush_regions(&regions).unwrap();
+
+        let mut expected = Framebuffer::new();
+        if lost {
+            gauge::draw_dial(&mut expected, &spec, &theme).unwrap();
+            gauge::draw_signal_lost(&mut expected, &spec, &theme, speed).unwrap();
+        } else {
+            gauge::render(&mut expected, &spec, speed).unwrap();
+        }
+        assert!(panel.bus().gram == expected.to_raw(), "panel out of sync at {} lost {}", speed, lost);
+    }
+}
+
+// a dial loaded from a raw image is what the needle moves over and what gets put back
+#[test]
+fn loaded_background_is_restored() {
+    let spec = GaugeSpec::default();
+    let theme = Theme::default();
+    // a blue ring and ticks, so a drawn dial would not pass for it
+    let mut image = Framebuffer::new();
+    let blue = Theme { ring: Rgb565::BLUE, ticks: Rgb565::BLUE, ..theme };
+    gauge::draw_dial(&mut image, &spec, &blue).unwrap();
+    let path = env::temp_dir().join(format!("speedometer-background-{}.raw", std::process::id()));
+    fs::write(&path, image.to_raw()).unwrap();
+
+    let dial = Dial::load(&path, spec.clone(), theme).unwrap();
+    fs::remove_file(&path).ok();
+    assert!(dial.background().pixels() == image.pixels());
+
+    let mut panel = Panel::new(MockBus::new());
+    let mut renderer = Renderer::new(dial);
+    for speed in [20.0, 95.0, 0.0, 60.5] {
+        let regions = renderer.draw(&mut panel, speed).unwrap();
+        panel.flush_regions(&regions).unwrap();
+
+        let mut expected = image.clone();
+        gauge::draw_needle(&mut expected, &spec, &theme, speed).unwrap();
+        gauge::draw_readout(&mut expected, &spec, &theme, speed).unwrap();
+        assert!(panel.bus().gram == expected.to_raw(), "panel out of sync at speed {}", speed);
+    }
+}
@@ -0,0 +1,107 @@
+// Wheel pulses from a mock generator instead of a GPIO interrupt
+
+use std::{
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use speedometer::{
+    source::{PulseConfig, PulseSource},
+    PulseInput, Speed, SpeedSource, SpeedUnit,
+};
+
+// Hands out the pulse times it is sent, like interrupts would arrive
+struct MockPulses(Receiver<Instant>);
+
+impl PulseInput for MockPulses {
+    fn wait_for_pulse(&mut self, timeout: Duration) -> Result<Option<Instant>> {
+        match self.0.recv_timeout(timeout) {
+            Ok(at) => Ok(Some(at)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(anyhow!("generator stopped")),
+        }
+    }
+}
+
+// 10 cm a pulse, so a pulse every 20 ms is 5 m/s
+fn config() -> PulseConfig {
+    PulseConfig { circumference_m: 0.4, pulses_per_rev: 4, zero_timeout_ms: 200, average: 2, ..PulseConfig::default() }
+}
+
+fn source(config: &PulseConfig) -> (Sender<Instant>, PulseSource<MockPulses>) {
+    let (tx, rx) = channel();
+    (tx, PulseSource::new(MockPulses(rx), config))
+}
+
+// Pulses at these many milliseconds from now
+fn pulse(tx: &Sender<Instant>, start: Instant, at_ms: &[f64]) {
+    for ms in at_ms {
+        tx.send(start + Duration::from_secs_f64(ms / 1000.0)).unwrap();
+    }
+}
+
+fn mps(source: &mut PulseSource<MockPulses>) -> f32 {
+    let speed = source.next_speed().unwrap();
+    assert_eq!(speed.unit, Some(SpeedUnit::MetersPerSecond));
+    speed.value
+}
+
+fn assert_near(value: f32, want: f32) {
+    assert!((value - want).abs() < 1e-3, "{} is not {}", value, want);
+}
+
+#[test]
+fn speed_from_the_pulse_period() {
+    let (tx, mut source) = source(&config());
+    pulse(&tx, Instant::now(), &[0.0, 20.0, 40.0, 60.0]);
+    // the first pulse has nothing to be timed against
+    for _ in 0..3 {
+        assert_near(mps(&mut source), 5.0);
+    }
+}
+
+#[test]
+fn averages_the_last_periods() {
+    let (tx, mut source) = source(&config());
+    pulse(&tx, Instant::now(), &[0.0, 20.0, 60.0, 160.0]);
+    assert_near(mps(&mut source), 5.0);
+    // (20 + 40) / 2 ms
+    assert_near(mps(&mut source), 0.1 / 0.030);
+    // (40 + 100) / 2 ms, the first period has dropped out
+    assert_near(mps(&mut source), 0.1 / 0.070);
+}
+
+#[test]
+fn ignores_contact_bounce() {
+    let (tx, mut source) = source(&PulseConfig { debounce_ms: 2, ..config() });
+    pulse(&tx, Instant::now(), &[0.0, 0.5, 1.2, 20.0, 20.3, 40.0, 41.9]);
+    assert_near(mps(&mut source), 5.0);
+    assert_near(mps(&mut source), 5.0);
+}
+
+#[test]
+fn drops_to_zero_when_the_wheel_stops() {
+    let (tx, mut source) = source(&config());
+    let start = Instant::now();
+    pulse(&tx, start, &[0.0, 20.0]);
+    assert_near(mps(&mut source), 5.0);
+
+    let stopped = Instant::now();
+    assert_eq!(source.next_speed().unwrap(), Speed::new(0.0, SpeedUnit::MetersPerSecond));
+    assert!(stopped.elapsed() >= Duration::from_millis(150));
+
+    // moving again, the first pulse only starts the clock
+    let start = Instant::now();
+    pulse(&tx, start, &[0.0, 10.0]);
+    assert_near(mps(&mut source), 10.0);
+}
+
+#[test]
+fn validates_the_wheel() {
+    assert!(PulseConfig::default().validate().is_ok());
+    assert!(PulseConfig { circumference_m: 0.0, ..config() }.validate().is_err());
+    assert!(PulseConfig { pulses_per_rev: 0, ..config() }.validate().is_err());
+    assert!(PulseConfig { average: 0, ..config() }.validate().is_err());
+    assert!(PulseConfig { pin: 40, ..config() }.validate().is_err());
+}
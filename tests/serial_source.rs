@@ -0,0 +1,160 @@
+// The ESP32 link: frames from the reference encoder, mangled on the way, and
+// through a pty into a `SerialSource`
+
+mod common;
+
+use std::{
+    env, fs,
+    io::Write,
+    os::unix::fs::symlink,
+    sync::mpsc::Receiver,
+    thread,
+    time::Duration,
+};
+
+use serialport::{SerialPort, TTYPort};
+use speedometer::{
+    link::{self, FrameError, Message, FLAG_SPEED_VALID, MAX_FRAME},
+    source::{SerialConfig, SerialSource},
+    Speed, SpeedUnit,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn frame(message: Message) -> Vec<u8> {
+    let mut buf = [0; MAX_FRAME];
+    let len = link::encode(&message, &mut buf);
+    buf[..len].to_vec()
+}
+
+fn telemetry(speed: f32) -> Message {
+    Message::Telemetry { speed, unit: Some(SpeedUnit::Kmh), rpm: 2500, flags: FLAG_SPEED_VALID }
+}
+
+// A pty standing in for the UART, with the source reading the far end on a thread
+fn connect(link_timeout: Duration) -> (TTYPort, Receiver<Result<Speed, String>>) {
+    let (esp32, pi) = TTYPort::pair().unwrap();
+    (esp32, common::readings(SerialSource::new(Box::new(pi), link_timeout)))
+}
+
+fn next(rx: &Receiver<Result<Speed, String>>) -> Result<Speed, String> {
+    rx.recv_timeout(TIMEOUT).expect("no reading")
+}
+
+#[test]
+fn crc_check_value() {
+    assert_eq!(link::crc16(b"123456789"), 0x29B1);
+}
+
+#[test]
+fn frames_round_trip() {
+    for message in [telemetry(88.5), Message::Heartbeat] {
+        let bytes = frame(message);
+        assert_eq!(link::decode(&bytes), Ok(Some((message, bytes.len()))));
+        // and nothing comes out of a frame that is not all there yet
+        assert_eq!(link::decode(&bytes[..bytes.len() - 1]), Ok(None));
+    }
+}
+
+#[test]
+fn corrupt_frames_are_rejected() {
+    let mut bytes = frame(telemetry(50.0));
+    bytes[5] ^= 0x10;
+    assert!(matches!(link::decode(&bytes), Err((FrameError::Crc { .. }, 1))));
+
+    assert_eq!(link::decode(&[0x00, 0x7E]), Err((FrameError::NoStart, 1)));
+    assert_eq!(link::decode(&[link::START, 0x01, 0xFF]), Err((FrameError::Length(0xFF), 1)));
+}
+
+#[test]
+fn reads_frames_from_the_port() {
+    let (mut esp32, rx) = connect(Duration::from_secs(10));
+    esp32.write_all(&frame(Message::Heartbeat)).unwrap();
+    esp32.write_all(&frame(telemetry(42.0))).unwrap();
+    assert_eq!(next(&rx), Ok(Speed::new(42.0, SpeedUnit::Kmh)));
+
+    // a frame split over two writes
+    let bytes = frame(telemetry(43.0));
+    esp32.write_all(&bytes[..4]).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    esp32.write_all(&bytes[4..]).unwrap();
+    assert_eq!(next(&rx), Ok(Speed::new(43.0, SpeedUnit::Kmh)));
+}
+
+#[test]
+fn resyncs_after_garbage_and_corruption() {
+    let (mut esp32, rx) = connect(Duration::from_secs(10));
+
+    let mut corrupt = frame(telemetry(99.0));
+    corrupt[4] ^= 0xFF;
+    // noise on the line, including a stray start byte, then a frame that went bad
+    let mut bytes = vec![0x00, 0x7E, 0x13, 0x7E, 0x7E];
+    bytes.extend(corrupt);
+    bytes.extend(frame(telemetry(60.0)));
+    // half a frame, cut off, then a good one
+    bytes.extend(&frame(telemetry(98.0))[..6]);
+    bytes.extend(frame(telemetry(61.0)));
+    esp32.write_all(&bytes).unwrap();
+
+    assert_eq!(next(&rx), Ok(Speed::new(60.0, SpeedUnit::Kmh)));
+    assert_eq!(next(&rx), Ok(Speed::new(61.0, SpeedUnit::Kmh)));
+}
+
+#[test]
+fn invalid_speed_is_an_error() {
+    let (mut esp32, rx) = connect(Duration::from_secs(10));
+    let no_fix = Message::Telemetry { speed: 0.0, unit: None, rpm: 800, flags: 0 };
+    esp32.write_all(&frame(no_fix)).unwrap();
+    assert!(next(&rx).unwrap_err().starts_with("no speed from"));
+}
+
+#[test]
+fn reports_a_lost_link_once() {
+    let (mut esp32, rx) = connect(Duration::from_millis(300));
+    esp32.write_all(&frame(telemetry(30.0))).unwrap();
+    assert_eq!(next(&rx), Ok(Speed::new(30.0, SpeedUnit::Kmh)));
+
+    let err = next(&rx).unwrap_err();
+    assert!(err.starts_with("serial link lost"), "{}", err);
+    // not again while it stays quiet
+    assert!(rx.recv_timeout(Duration::from_millis(800)).is_err());
+
+    // and comes back with the next frame
+    esp32.write_all(&frame(Message::Heartbeat)).unwrap();
+    esp32.write_all(&frame(telemetry(31.0))).unwrap();
+    assert_eq!(next(&rx), Ok(Speed::new(31.0, SpeedUnit::Kmh)));
+}
+
+#[test]
+fn opens_the_port_again_after_it_went_away() {
+    // a stable name for the port, like /dev/serial/by-id, pointing at whichever pty is current
+    let dir = env::temp_dir().join(format!("speedometer-serial-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let port = dir.join("esp32");
+    let (mut esp32, pi) = TTYPort::pair().unwrap();
+    fs::remove_file(&port).ok();
+    symlink(pi.name().unwrap(), &port).unwrap();
+
+    let config = SerialConfig { port: port.to_string_lossy().into_owned(), baud: 115_200, link_timeout_ms: 10_000 };
+    let rx = common::readings(SerialSource::open(&config).unwrap());
+    drop(pi);
+    esp32.write_all(&frame(telemetry(30.0))).unwrap();
+    assert_eq!(next(&rx), Ok(Speed::new(30.0, SpeedUnit::Kmh)));
+
+    // unplugged: said once, then quiet rather than failing as fast as it can
+    drop(esp32);
+    assert!(next(&rx).is_err());
+    assert!(rx.recv_timeout(Duration::from_millis(1500)).is_err());
+
+    // plugged back in
+    let (mut esp32, pi) = TTYPort::pair().unwrap();
+    fs::remove_file(&port).unwrap();
+    symlink(pi.name().unwrap(), &port).unwrap();
+    let speed = (0..50).find_map(|_| {
+        esp32.write_all(&frame(telemetry(31.0))).unwrap();
+        rx.recv_timeout(Duration::from_millis(200)).ok()
+    });
+    assert_eq!(speed, Some(Ok(Speed::new(31.0, SpeedUnit::Kmh))));
+    drop(pi);
+    fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,54 @@
+// Parsing the speed from text, as it comes out of the watched file
+
+use speedometer::{Speed, SpeedUnit};
+
+fn parse(s: &str) -> Speed {
+    s.parse::<Speed>().unwrap_or_else(|e| panic!("{:?} did not parse: {}", s, e))
+}
+
+#[test]
+fn bare_numbers() {
+    assert_eq!(parse("42"), Speed::bare(42.0));
+    assert_eq!(parse(" 42\n"), Speed::bare(42.0));
+    assert_eq!(parse("7.25"), Speed::bare(7.25));
+    assert_eq!(parse("1e2"), Speed::bare(100.0));
+    assert_eq!(parse("-0"), Speed::bare(0.0));
+    assert_eq!(format!("{}", parse("-0").value), "0");
+}
+
+#[test]
+fn numbers_with_units() {
+    assert_eq!(parse("42 mph"), Speed::new(42.0, SpeedUnit::Mph));
+    assert_eq!(parse("67.6 km/h"), Speed::new(67.6, SpeedUnit::Kmh));
+    assert_eq!(parse("67.6km/h"), Speed::new(67.6, SpeedUnit::Kmh));
+    assert_eq!(parse("18.8 m/s"), Speed::new(18.8, SpeedUnit::MetersPerSecond));
+    assert_eq!(parse("12 KTS"), Speed::new(12.0, SpeedUnit::Knots));
+}
+
+#[test]
+fn rejects_garbage() {
+    for s in ["", "  \n", "abc", "NaN", "nan", "inf", "-inf", "infinity", "1e99", "-3", "42 furlongs", "4 2", "42..0", "0x10", "mph"] {
+        assert!(s.parse::<Speed>().is_err(), "{:?} should not parse", s);
+    }
+    let long = "1".repeat(100);
+    assert!(long.parse::<Speed>().is_err());
+}
+
+#[test]
+fn converts_to_the_gauge_unit() {
+    let mph = "mi/hr".parse::<SpeedUnit>().ok();
+    let kmh = "km/h".parse::<SpeedUnit>().ok();
+    let close = |a: f32, b: f32| (a - b).abs() < 0.01;
+
+    assert!(close(parse("67.6 km/h").in_unit(mph).unwrap(), 42.0));
+    assert!(close(parse("18.8 m/s").in_unit(mph).unwrap(), 42.05));
+    assert!(close(parse("42 mph").in_unit(kmh).unwrap(), 67.59));
+    assert!(close(parse("10 kn").in_unit(kmh).unwrap(), 18.52));
+    // bare numbers are already in the gauge's unit
+    assert_eq!(parse("42").in_unit(kmh).unwrap(), 42.0);
+    // a speed cannot go on a gauge that is not in a speed unit
+    assert!(parse("42 mph").in_unit(None).is_err());
+    assert_eq!(parse("3500").in_unit(None).unwrap(), 3500.0);
+    // too big once converted
+    assert!(parse("3e38 mph").in_unit(kmh).is_err());
+}
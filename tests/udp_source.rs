@@ -0,0 +1,111 @@
+// Sends speed datagrams to a `UdpSource` over loopback
+
+mod common;
+
+use std::{
+    net::{SocketAddr, UdpSocket},
+    sync::mpsc::Receiver,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use speedometer::{
+    source::{udp::Packet, UdpConfig, UdpSource},
+    Speed, SpeedUnit,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// Starts a source on a free port, readings (or error messages) come out of the receiver
+fn listen(max_age_ms: u64) -> (SocketAddr, Receiver<Result<Speed, String>>) {
+    let config = UdpConfig { bind: "127.0.0.1:0".to_string(), max_age_ms };
+    let source = UdpSource::bind(&config).unwrap();
+    let addr = source.local_addr().unwrap();
+    (addr, common::readings(source))
+}
+
+fn send(to: SocketAddr, datagram: &[u8]) {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.send_to(datagram, to).unwrap();
+}
+
+fn packet(seq: u32, timestamp_ms: u64, value: f32) -> [u8; Packet::LEN] {
+    Packet { seq, timestamp_ms, speed: Speed::bare(value) }.encode()
+}
+
+fn next(rx: &Receiver<Result<Speed, String>>) -> Speed {
+    rx.recv_timeout(TIMEOUT).expect("no reading").unwrap()
+}
+
+#[test]
+fn text_datagrams() {
+    let (addr, rx) = listen(0);
+    send(addr, b"42\n");
+    send(addr, b"67.6 km/h");
+    assert_eq!(next(&rx), Speed::bare(42.0));
+    assert_eq!(next(&rx), Speed::new(67.6, SpeedUnit::Kmh));
+}
+
+#[test]
+fn binary_packets_round_trip() {
+    let sent = Packet { seq: 7, timestamp_ms: 1_700_000_000_000, speed: Speed::new(18.8, SpeedUnit::MetersPerSecond) };
+    assert_eq!(Packet::decode(&sent.encode()).unwrap(), sent);
+
+    let (addr, rx) = listen(0);
+    send(addr, &sent.encode());
+    assert_eq!(next(&rx), sent.speed);
+}
+
+#[test]
+fn drops_late_and_repeated_packets() {
+    let (addr, rx) = listen(0);
+    for (seq, value) in [(1, 10.0), (3, 30.0), (2, 20.0), (3, 30.0), (4, 40.0)] {
+        send(addr, &packet(seq, 0, value));
+    }
+    assert_eq!(next(&rx).value, 10.0);
+    assert_eq!(next(&rx).value, 30.0);
+    assert_eq!(next(&rx).value, 40.0);
+}
+
+#[test]
+fn follows_sequence_wrap_and_sender_restart() {
+    let (addr, rx) = listen(0);
+    send(addr, &packet(u32::MAX, 0, 1.0));
+    send(addr, &packet(0, 0, 2.0));
+    assert_eq!(next(&rx).value, 1.0);
+    assert_eq!(next(&rx).value, 2.0);
+
+    // a restarted sender counts from 0 again
+    send(addr, &packet(5000, 0, 3.0));
+    send(addr, &packet(0, 0, 4.0));
+    assert_eq!(next(&rx).value, 3.0);
+    assert_eq!(next(&rx).value, 4.0);
+}
+
+#[test]
+fn drops_old_packets_when_asked() {
+    let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    let (addr, rx) = listen(500);
+    send(addr, &packet(1, now_ms - 5_000, 10.0));
+    send(addr, &packet(2, now_ms, 20.0));
+    // no clock on the sender, nothing to go by
+    send(addr, &packet(3, 0, 30.0));
+    assert_eq!(next(&rx).value, 20.0);
+    assert_eq!(next(&rx).value, 30.0);
+}
+
+#[test]
+fn bad_datagrams_are_errors() {
+    let (addr, rx) = listen(0);
+    let mut nan = packet(1, 0, 0.0);
+    nan[16..20].copy_from_slice(&f32::NAN.to_be_bytes());
+
+    for datagram in [&b"garbage"[..], &b"SP\x01"[..], &nan[..], &[0xff, 0xfe, 0x00][..]] {
+        send(addr, datagram);
+        let err = rx.recv_timeout(TIMEOUT).unwrap().unwrap_err();
+        assert!(err.starts_with("Bad datagram from 127.0.0.1"), "{}", err);
+    }
+
+    // and the source keeps going
+    send(addr, b"12");
+    assert_eq!(next(&rx).value, 12.0);
+}
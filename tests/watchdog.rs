@@ -0,0 +1,44 @@
+// The signal watchdog on made up times
+
+use std::time::{Duration, Instant};
+
+use speedometer::watchdog::Watchdog;
+
+fn ms(start: Instant, ms: u64) -> Instant {
+    start + Duration::from_millis(ms)
+}
+
+#[test]
+fn lost_after_the_timeout_and_back_with_the_next_sample() {
+    let t = Instant::now();
+    let mut watchdog = Watchdog::new(Some(Duration::from_millis(500)), t);
+
+    watchdog.feed(ms(t, 100));
+    assert!(!watchdog.check(ms(t, 599)));
+    assert!(!watchdog.lost());
+    assert!(watchdog.check(ms(t, 600)));
+    assert!(watchdog.lost());
+    // said once
+    assert!(!watchdog.check(ms(t, 700)));
+    assert!(watchdog.lost());
+
+    watchdog.feed(ms(t, 800));
+    assert!(watchdog.check(ms(t, 800)));
+    assert!(!watchdog.lost());
+}
+
+#[test]
+fn nothing_at_all_is_lost_too() {
+    let t = Instant::now();
+    let mut watchdog = Watchdog::new(Some(Duration::from_millis(500)), t);
+    assert!(!watchdog.check(ms(t, 499)));
+    assert!(watchdog.check(ms(t, 500)));
+}
+
+#[test]
+fn without_a_timeout_it_never_goes_off() {
+    let t = Instant::now();
+    let mut watchdog = Watchdog::new(None, t);
+    assert!(!watchdog.check(ms(t, 3_600_000)));
+    assert!(!watchdog.lost());
+}